@@ -0,0 +1,42 @@
+// Copyright (C) 2019 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{fs, path::Path, process::Command};
+
+/// Embeds the git commit which the tool is built from, it is recorded in the manifest.
+fn main() {
+    let commit = Command::new("git")
+        .args(&["describe", "--always", "--dirty", "--abbrev=40"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_owned())
+        .unwrap_or_else(|| "unknown".to_owned());
+    println!("cargo:rustc-env=GBGC_GIT_COMMIT={}", commit);
+    // Without any watched file, the script is rerun for every change of the package.
+    println!("cargo:rerun-if-changed=build.rs");
+    // A new commit only changes the ref which HEAD points to, or the packed refs.
+    // A missing file is always treated as changed, so only the existed ones are watched, and
+    // nothing of git is watched when the source is not in a git repository.
+    let head_ref = fs::read_to_string(".git/HEAD").ok().and_then(|head| {
+        head.trim()
+            .strip_prefix("ref: ")
+            .map(|r| format!(".git/{}", r))
+    });
+    for path in [".git/HEAD", ".git/index"]
+        .iter()
+        .cloned()
+        .chain(head_ref.iter().map(String::as_str))
+        .chain(Some(".git/packed-refs"))
+    {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+}
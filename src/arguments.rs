@@ -6,63 +6,449 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::{convert::TryFrom, fs, path};
+use std::{convert::TryFrom, fs, path, time};
 
-use parking_lot::RwLock;
 use property::Property;
 
 use uckb_jsonrpc_client::url;
 
 use crate::{
-    constants,
     error::{Error, Result},
+    module::{asset, chain::ChainConfig, hash, network::Network},
+    output::{self, Output},
+    retry::RetryPolicy,
+    target::{Averaging, TargetPolicy},
 };
 
+pub enum Command {
+    Generate(Arguments),
+    ExportBlocks(ExportArguments),
+    Simulate(SimulateArguments),
+    Diff(DiffArguments),
+}
+
+/// The arguments for JSON-RPC servers, they are shared by all subcommands.
+#[derive(Property)]
+pub struct RpcArguments {
+    urls: Vec<url::Url>,
+    quorum: usize,
+    retry: RetryPolicy,
+    concurrency: usize,
+    rate: u32,
+}
+
 #[derive(Property)]
 pub struct Arguments {
-    url: url::Url,
+    rpc: RpcArguments,
+    network: Network,
+    chain: ChainConfig,
     epoch: u64,
-    output: RwLock<fs::File>,
+    output: Output,
+    // The side outputs are next to the output, they are not written if the output is stdout.
+    audit: Option<Output>,
+    target: TargetPolicy,
+    target_report: Option<Output>,
+    cells_csv: Option<Output>,
+    cells_json: Option<Output>,
+    manifest: Option<Output>,
+    strict: bool,
+    skipped: Option<Output>,
+    fallback: Option<asset::Owner>,
+    template: Option<String>,
+    vars: serde_json::Map<String, serde_json::Value>,
+    block_json: Option<Output>,
+    block_mol: Option<Output>,
+}
+
+#[derive(Property)]
+pub struct ExportArguments {
+    rpc: RpcArguments,
+    chain: ChainConfig,
+    from: u64,
+    to: u64,
+    // The directory is created when the blocks are exported.
+    output_dir: path::PathBuf,
+    force: bool,
+}
+
+#[derive(Property)]
+pub struct SimulateArguments {
+    rpc: RpcArguments,
+    chain: ChainConfig,
+    epoch: u64,
+    window: u64,
+    target: u32,
+    epoch_length: u64,
+    output: Option<Output>,
+}
+
+#[derive(Property)]
+pub struct DiffArguments {
+    before: String,
+    after: String,
+    output: Output,
+    json: bool,
+}
+
+impl Arguments {
+    /// All outputs, they are committed together when the generation succeeds.
+    pub fn outputs(&self) -> Vec<&Output> {
+        let mut outputs = vec![&self.output];
+        for side in &[
+            &self.audit,
+            &self.target_report,
+            &self.cells_csv,
+            &self.cells_json,
+            &self.manifest,
+            &self.skipped,
+            &self.block_json,
+            &self.block_mol,
+        ] {
+            outputs.extend(side.iter());
+        }
+        outputs
+    }
+}
+
+impl SimulateArguments {
+    pub fn outputs(&self) -> Vec<&Output> {
+        self.output.iter().collect()
+    }
 }
 
-pub fn build_commandline() -> Result<Arguments> {
+impl DiffArguments {
+    pub fn outputs(&self) -> Vec<&Output> {
+        vec![&self.output]
+    }
+}
+
+pub fn build_commandline() -> Result<Command> {
     let yaml = clap::load_yaml!("cli.yaml");
     let matches = clap::App::from_yaml(yaml).get_matches();
-    Arguments::try_from(&matches)
+    match matches.subcommand() {
+        ("export-blocks", Some(submatches)) => {
+            ExportArguments::try_from(submatches).map(Command::ExportBlocks)
+        }
+        ("simulate", Some(submatches)) => {
+            SimulateArguments::try_from(submatches).map(Command::Simulate)
+        }
+        ("diff", Some(submatches)) => DiffArguments::try_from(submatches).map(Command::Diff),
+        _ => Arguments::try_from(&matches).map(Command::Generate),
+    }
 }
 
-impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for Arguments {
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for RpcArguments {
     type Error = Error;
     fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
-        let url = matches
-            .value_of("url")
+        let urls = matches
+            .values_of("url")
+            .ok_or_else(|| Error::Unreachable("no argument 'url'".to_owned()))?
             .map(|url_str| url::Url::parse(url_str))
+            .collect::<::std::result::Result<Vec<_>, _>>()?;
+        let quorum = matches
+            .value_of("quorum")
+            .map(|num_str| num_str.parse::<usize>())
+            .transpose()?
+            .unwrap_or_else(|| urls.len() / 2 + 1);
+        if quorum == 0 || quorum > urls.len() {
+            return Err(Error::InvalidQuorum(quorum, urls.len()));
+        }
+        let max_attempts = matches
+            .value_of("rpc-attempts")
+            .map(|num_str| num_str.parse::<u32>())
+            .transpose()?
+            .ok_or_else(|| Error::Unreachable("no argument 'rpc-attempts'".to_owned()))?;
+        let timeout = matches
+            .value_of("rpc-timeout")
+            .map(|num_str| num_str.parse::<u64>().map(time::Duration::from_secs))
+            .transpose()?
+            .ok_or_else(|| Error::Unreachable("no argument 'rpc-timeout'".to_owned()))?;
+        let retry = RetryPolicy::new(max_attempts.max(1), timeout);
+        let concurrency = matches
+            .value_of("rpc-concurrency")
+            .map(|num_str| num_str.parse::<usize>())
+            .transpose()?
+            .ok_or_else(|| Error::Unreachable("no argument 'rpc-concurrency'".to_owned()))?
+            .max(1);
+        let rate = matches
+            .value_of("rpc-rate")
+            .map(|num_str| num_str.parse::<u32>())
+            .transpose()?
+            .ok_or_else(|| Error::Unreachable("no argument 'rpc-rate'".to_owned()))?;
+        Ok(Self {
+            urls,
+            quorum,
+            retry,
+            concurrency,
+            rate,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for Arguments {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let rpc = RpcArguments::try_from(matches)?;
+        let network = parse_network(matches)?;
+        let chain = ChainConfig::load(matches.value_of("chain"), network)?;
+        let window = matches
+            .value_of("target-window")
+            .map(|num_str| num_str.parse::<u64>())
+            .transpose()?
+            .unwrap_or(chain.consensus.epoch_avg_count)
+            .max(1);
+        let averaging = matches
+            .value_of("target-averaging")
+            .map(str::parse::<Averaging>)
             .transpose()?
-            .ok_or_else(|| Error::Unreachable("no argument 'url'".to_owned()))?;
+            .unwrap_or(Averaging::Mean);
+        let fixed = matches
+            .value_of("target-fixed")
+            .map(parse_compact_target)
+            .transpose()?;
+        let target = TargetPolicy::new(window, averaging, fixed);
         let epoch = matches
             .value_of("epoch")
             .map(|num_str| num_str.parse::<u64>().map(|num| num + 1))
             .transpose()?
             .ok_or_else(|| Error::Unreachable("no argument 'epoch'".to_owned()))
             .and_then(|epoch| {
-                if epoch < constants::EPOCH_AVG_COUNT {
-                    Err(Error::EpochTooSmall(epoch, constants::EPOCH_AVG_COUNT))
+                if epoch < window {
+                    Err(Error::EpochTooSmall(epoch, window))
                 } else {
                     Ok(epoch)
                 }
             })?;
+        let output_path = matches
+            .value_of("output")
+            .ok_or_else(|| Error::Unreachable("no argument 'output'".to_owned()))?;
+        let force = matches.is_present("force");
+        let output = Output::create(output_path, force)?;
+        // The side outputs are always saved beside the spec, so they follow its `--force`.
+        let create_side = |suffix: &str| {
+            if output.is_stdout() {
+                Ok(None)
+            } else {
+                Output::create(&format!("{}.{}", output_path, suffix), force)
+                    .map(Some)
+                    .map_err(|err| match err {
+                        Error::OutputExisted(path) => {
+                            Error::SideOutputExisted(path, output_path.to_owned())
+                        }
+                        err => err,
+                    })
+            }
+        };
+        let audit = create_side("audit.json")?;
+        let target_report = create_side("target.json")?;
+        let cells_csv = create_side("cells.csv")?;
+        let cells_json = create_side("cells.json")?;
+        let manifest = create_side("manifest.json")?;
+        let strict = matches.is_present("strict");
+        let skipped = matches
+            .value_of("skipped")
+            .map(|path| Output::create(path, force))
+            .transpose()?;
+        let fallback = matches
+            .value_of("fallback-address")
+            .map(|address| {
+                hash::extract_from_address_with_prefixes(address, network.address_prefixes())
+                    .and_then(|result| result.ok())
+                    .map(asset::Owner::new_single)
+                    .ok_or_else(|| Error::InvalidAddress(address.to_owned()))
+            })
+            .transpose()?;
+        let template = matches.value_of("template").map(ToOwned::to_owned);
+        let mut vars = matches
+            .value_of("vars")
+            .map(load_vars)
+            .transpose()?
+            .unwrap_or_default();
+        for var in matches.values_of("var").into_iter().flatten() {
+            let (key, value) = parse_var(var)?;
+            vars.insert(key, value);
+        }
+        let emit_block = matches.value_of("emit-block");
+        let block_json = emit_block
+            .map(|prefix| Output::create(&format!("{}.json", prefix), force))
+            .transpose()?;
+        let block_mol = emit_block
+            .map(|prefix| Output::create(&format!("{}.mol", prefix), force))
+            .transpose()?;
+        let args = Self {
+            rpc,
+            network,
+            chain,
+            epoch,
+            output,
+            audit,
+            target,
+            target_report,
+            cells_csv,
+            cells_json,
+            manifest,
+            strict,
+            skipped,
+            fallback,
+            template,
+            vars,
+            block_json,
+            block_mol,
+        };
+        output::check_stdout(&args.outputs())?;
+        Ok(args)
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for ExportArguments {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let rpc = RpcArguments::try_from(matches)?;
+        let chain = ChainConfig::load(matches.value_of("chain"), parse_network(matches)?)?;
+        let from = matches
+            .value_of("from")
+            .map(|num_str| num_str.parse::<u64>())
+            .transpose()?
+            .ok_or_else(|| Error::Unreachable("no argument 'from'".to_owned()))?;
+        let to = matches
+            .value_of("to")
+            .map(|num_str| num_str.parse::<u64>())
+            .transpose()?
+            .ok_or_else(|| Error::Unreachable("no argument 'to'".to_owned()))?;
+        if from == 0 || from > to {
+            return Err(Error::InvalidRange(from, to));
+        }
+        let output_dir = matches
+            .value_of("output")
+            .map(path::PathBuf::from)
+            .ok_or_else(|| Error::Unreachable("no argument 'output'".to_owned()))?;
+        let force = matches.is_present("force");
+        Ok(Self {
+            rpc,
+            chain,
+            from,
+            to,
+            output_dir,
+            force,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for SimulateArguments {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let rpc = RpcArguments::try_from(matches)?;
+        let chain = ChainConfig::load(matches.value_of("chain"), parse_network(matches)?)?;
+        let window = matches
+            .value_of("target-window")
+            .map(|num_str| num_str.parse::<u64>())
+            .transpose()?
+            .unwrap_or(chain.consensus.epoch_avg_count)
+            .max(1);
+        let epoch = matches
+            .value_of("epoch")
+            .map(|num_str| num_str.parse::<u64>())
+            .transpose()?
+            .ok_or_else(|| Error::Unreachable("no argument 'epoch'".to_owned()))?;
+        if epoch + 1 < window {
+            return Err(Error::EpochTooSmall(epoch + 1, window));
+        }
+        let target = matches
+            .value_of("target")
+            .map(parse_compact_target)
+            .transpose()?
+            .ok_or_else(|| Error::Unreachable("no argument 'target'".to_owned()))?;
+        let epoch_length = matches
+            .value_of("epoch-length")
+            .map(|num_str| num_str.parse::<u64>())
+            .transpose()?
+            .unwrap_or(chain.genesis.epoch_length)
+            .max(1);
         let output = matches
             .value_of("output")
-            .ok_or_else(|| Error::Unreachable("no argument 'output'".to_owned()))
-            .and_then(|path_str| {
-                let path = path::Path::new(path_str);
-                if path.exists() {
-                    Err(Error::OutputExisted(path_str.to_owned()))
-                } else {
-                    let file = fs::OpenOptions::new().create(true).write(true).open(path)?;
-                    Ok(RwLock::new(file))
-                }
-            })?;
-        Ok(Self { url, epoch, output })
+            .map(|path| Output::create(path, matches.is_present("force")))
+            .transpose()?;
+        Ok(Self {
+            rpc,
+            chain,
+            epoch,
+            window,
+            target,
+            epoch_length,
+            output,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for DiffArguments {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let before = matches
+            .value_of("before")
+            .ok_or_else(|| Error::Unreachable("no argument 'before'".to_owned()))?
+            .to_owned();
+        let after = matches
+            .value_of("after")
+            .ok_or_else(|| Error::Unreachable("no argument 'after'".to_owned()))?
+            .to_owned();
+        let output_path = matches
+            .value_of("output")
+            .ok_or_else(|| Error::Unreachable("no argument 'output'".to_owned()))?;
+        let output = Output::create(output_path, matches.is_present("force"))?;
+        let json = matches.is_present("json");
+        Ok(Self {
+            before,
+            after,
+            output,
+            json,
+        })
+    }
+}
+
+fn is_var_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parses a template variable as `key=value`, the value is a string.
+fn parse_var(var: &str) -> Result<(String, serde_json::Value)> {
+    let mut split = var.splitn(2, '=');
+    match (split.next(), split.next()) {
+        (Some(key), Some(value)) if is_var_name(key) => {
+            Ok((key.to_owned(), serde_json::Value::String(value.to_owned())))
+        }
+        _ => Err(Error::InvalidVariable(format!("'{}'", var))),
+    }
+}
+
+/// Loads template variables from a TOML file, the values could be any TOML values.
+fn load_vars(path: &str) -> Result<serde_json::Map<String, serde_json::Value>> {
+    let content = fs::read_to_string(path)?;
+    let table: toml::value::Table = toml::from_str(&content)?;
+    let mut vars = serde_json::Map::new();
+    for (key, value) in table {
+        if !is_var_name(&key) {
+            return Err(Error::InvalidVariable(format!("'{}' in {}", key, path)));
+        }
+        vars.insert(key, serde_json::to_value(value)?);
+    }
+    Ok(vars)
+}
+
+fn parse_network(matches: &clap::ArgMatches) -> Result<Network> {
+    matches
+        .value_of("network")
+        .ok_or_else(|| Error::Unreachable("no argument 'network'".to_owned()))
+        .and_then(str::parse)
+}
+
+/// Parses a compact target in hex, such as `0x1a08a97e`.
+fn parse_compact_target(target_str: &str) -> Result<u32> {
+    if target_str.starts_with("0x") {
+        u32::from_str_radix(&target_str[2..], 16)
+            .ok()
+            .filter(|target| *target != 0)
+            .ok_or_else(|| Error::InvalidCompactTarget(target_str.to_owned()))
+    } else {
+        Err(Error::InvalidCompactTarget(target_str.to_owned()))
     }
 }
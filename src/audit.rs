@@ -0,0 +1,204 @@
+// Copyright (C) 2019 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{fmt, io};
+
+use property::Property;
+use serde_derive::Serialize;
+
+use crate::error::{InvariantError, Result};
+
+#[derive(Debug, Property, Serialize)]
+pub struct Invariant {
+    name: String,
+    passed: bool,
+    expected: String,
+    actual: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Audit {
+    passed: bool,
+    invariants: Vec<Invariant>,
+}
+
+impl fmt::Display for Invariant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {}: expected {}, actual {}",
+            if self.passed { "pass" } else { "FAIL" },
+            self.name,
+            self.expected,
+            self.actual
+        )
+    }
+}
+
+impl Default for Audit {
+    fn default() -> Self {
+        Self {
+            passed: true,
+            invariants: Vec::new(),
+        }
+    }
+}
+
+impl Audit {
+    pub fn invariants(&self) -> &[Invariant] {
+        &self.invariants[..]
+    }
+
+    pub fn is_passed(&self) -> bool {
+        self.passed
+    }
+
+    fn record(&mut self, name: &str, passed: bool, expected: String, actual: String) -> bool {
+        let invariant = Invariant {
+            name: name.to_owned(),
+            passed,
+            expected,
+            actual,
+        };
+        if passed {
+            log::info!("audit {}", invariant);
+        } else {
+            log::error!("audit {}", invariant);
+            self.passed = false;
+        }
+        self.invariants.push(invariant);
+        passed
+    }
+
+    /// Checks `actual == expected`.
+    pub fn check_eq<T>(&mut self, name: &str, expected: T, actual: T) -> bool
+    where
+        T: PartialEq + fmt::Display,
+    {
+        let passed = expected == actual;
+        self.record(name, passed, expected.to_string(), actual.to_string())
+    }
+
+    /// Checks `actual <= limit`.
+    pub fn check_le<T>(&mut self, name: &str, limit: T, actual: T) -> bool
+    where
+        T: PartialOrd + fmt::Display,
+    {
+        let passed = actual <= limit;
+        self.record(name, passed, format!("<= {}", limit), actual.to_string())
+    }
+
+    /// Checks `lower <= actual <= upper`.
+    pub fn check_range<T>(&mut self, name: &str, lower: T, upper: T, actual: T) -> bool
+    where
+        T: PartialOrd + fmt::Display,
+    {
+        let passed = lower <= actual && actual <= upper;
+        self.record(
+            name,
+            passed,
+            format!("[{}, {}]", lower, upper),
+            actual.to_string(),
+        )
+    }
+
+    pub fn write_json<W: io::Write>(&self, writer: W) -> Result<()> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    /// Returns the first failed invariant as an error, all failures are already logged.
+    pub fn finish(&self) -> Result<()> {
+        let mut failed = self.invariants.iter().filter(|invariant| !invariant.passed);
+        if let Some(invariant) = failed.next() {
+            let others = failed.count();
+            if others > 0 {
+                log::error!("audit: {} more invariants failed", others);
+            }
+            Err(InvariantError::new(&invariant.name, &invariant.expected, &invariant.actual).into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    #[test]
+    fn checks_are_recorded() {
+        let mut audit = Audit::default();
+        assert!(audit.check_eq("eq", 1, 1));
+        assert!(!audit.check_eq("not eq", 1, 2));
+        assert!(audit.check_le("le", 2, 2));
+        assert!(!audit.check_le("not le", 2, 3));
+        assert!(audit.check_range("in range", 1, 3, 1));
+        assert!(audit.check_range("upper bound", 1, 3, 3));
+        assert!(!audit.check_range("below range", 1, 3, 0));
+        assert!(!audit.check_range("above range", 1, 3, 4));
+        let passed = audit
+            .invariants()
+            .iter()
+            .map(|invariant| (invariant.name(), invariant.passed()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            passed,
+            vec![
+                ("eq", true),
+                ("not eq", false),
+                ("le", true),
+                ("not le", false),
+                ("in range", true),
+                ("upper bound", true),
+                ("below range", false),
+                ("above range", false),
+            ]
+        );
+        let invariant = &audit.invariants()[5];
+        assert_eq!(invariant.expected(), "[1, 3]");
+        assert_eq!(invariant.actual(), "3");
+        assert_eq!(audit.invariants()[3].expected(), "<= 2");
+    }
+
+    #[test]
+    fn finish_passed() {
+        let mut audit = Audit::default();
+        assert!(audit.finish().is_ok());
+        audit.check_eq("eq", 1, 1);
+        assert!(audit.is_passed());
+        assert!(audit.finish().is_ok());
+    }
+
+    #[test]
+    fn finish_failed() {
+        let mut audit = Audit::default();
+        audit.check_eq("first", 1, 2);
+        audit.check_eq("passed", 1, 1);
+        audit.check_le("second", 1, 2);
+        assert!(!audit.is_passed());
+        match audit.finish() {
+            Err(Error::Invariant(err)) => assert_eq!(err.name(), "first"),
+            _ => panic!("the audit should fail"),
+        }
+    }
+
+    #[test]
+    fn write_json() {
+        let mut audit = Audit::default();
+        audit.check_eq("eq", 1, 2);
+        let mut buffer = Vec::new();
+        audit.write_json(&mut buffer).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+        assert_eq!(json["passed"], false);
+        assert_eq!(json["invariants"][0]["name"], "eq");
+        assert_eq!(json["invariants"][0]["expected"], "1");
+        assert_eq!(json["invariants"][0]["actual"], "2");
+    }
+}
@@ -6,158 +6,605 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::{collections::HashMap, sync::Arc, thread, time};
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    result,
+    sync::Arc,
+    time,
+};
 
-use futures::future::Future;
-use parking_lot::RwLock;
+use failure::Fail;
+use futures::{
+    future,
+    stream::{self, StreamExt},
+};
 use property::Property;
-use tokio::runtime;
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+use tokio::{runtime, signal};
 
 use uckb_jsonrpc_client::{
-    client::{CkbAsyncClient, CkbSyncClient},
-    interfaces::types::{core, packed, prelude::*, utilities, U256},
+    interfaces::types::{core, packed, prelude::*, rpc},
+    url,
 };
 
 use crate::{
     arguments, constants,
-    error::{Error, Result},
+    error::{Error, Result, RpcError},
+    jsonrpc::HttpClient,
+    module::{
+        chain::ChainConfig,
+        config,
+        reward::{BlockReward, Component},
+        token::Token,
+    },
+    retry, target,
 };
 
 #[derive(Property)]
 pub struct ChainData {
-    rewards: HashMap<Vec<u8>, u64>,
+    blocks: BTreeMap<u64, MinedBlock>,
     header: core::HeaderView,
-    diff_avg: U256,
+    epochs: Vec<target::Epoch>,
+}
+
+/// The miner and the full reward of a block.
+#[derive(Clone, Property)]
+pub struct MinedBlock {
+    lock: packed::Script,
+    reward: BlockReward,
 }
 
-type BlockData = HashMap<u64, (Vec<u8>, u64)>;
+#[derive(Debug, Fail)]
+#[fail(display = "invalid cellbase: {}", _0)]
+pub struct InvalidCellbase(String);
 
-fn sleep_millis(millis: u64) {
-    let millis = time::Duration::from_millis(millis);
-    thread::sleep(millis);
+#[derive(Debug, Fail)]
+pub enum ChainError {
+    #[fail(display = "the chain is still reorganizing after {} rounds", _0)]
+    Unstable(u32),
+    #[fail(display = "block {} is not a child of the block before it", _0)]
+    Disconnected(u64),
+    #[fail(
+        display = "the reward of block {} is not available, the tip is {}",
+        _0, _1
+    )]
+    Unavailable(u64, u64),
+    #[fail(
+        display = "no quorum, only {} of {} endpoints agree (quorum is {})",
+        _0, _1, _2
+    )]
+    NoQuorum(usize, usize, usize),
+    #[fail(display = "epoch {} is not finished, the tip is in epoch {}", _0, _1)]
+    Unfinished(u64, u64),
 }
 
-fn fetch_for_number(
-    num_reward: u64,
-    rt: &mut runtime::Runtime,
-    cli: Arc<CkbAsyncClient>,
-    cnt: Arc<RwLock<u64>>,
-    rec: Arc<RwLock<BlockData>>,
-) {
-    let num_block = num_reward - constants::CONFIRMATIONS;
-    let cli2 = Arc::clone(&cli);
-    let fut = cli
-        .block_by_number(num_block)
-        .and_then(move |block| {
-            let cellbase_input = block
-                .transaction(0)
-                .unwrap()
-                .witnesses()
-                .get(0)
-                .unwrap()
-                .raw_data();
-            let address = packed::CellbaseWitnessReader::from_slice(&cellbase_input)
-                .unwrap()
-                .lock()
-                .args()
-                .raw_data()
-                .to_owned();
-            cli.block_hash(Some(num_reward)).map(|hash| (hash, address))
+/// The miner and the primary reward of a block, with the hashes to verify the chain.
+#[derive(Clone, PartialEq, Property)]
+pub struct BlockRecord {
+    number: u64,
+    hash: packed::Byte32,
+    parent_hash: packed::Byte32,
+    // The hash of the block which is `confirmations` later, its cellbase has the reward.
+    reward_hash: packed::Byte32,
+    lock: packed::Script,
+    reward: BlockReward,
+}
+
+/// A JSON-RPC client, all requests are retried by the policy.
+#[derive(Clone)]
+pub struct Client {
+    url: url::Url,
+    inner: Arc<HttpClient>,
+    policy: retry::RetryPolicy,
+    confirmations: u64,
+}
+
+/// The chain data which is fetched from one endpoint.
+struct Endpoint {
+    fetcher: Fetcher,
+    header: core::HeaderView,
+    epochs: Vec<target::Epoch>,
+}
+
+impl Client {
+    pub fn new(url: url::Url, policy: retry::RetryPolicy, confirmations: u64) -> Self {
+        Self {
+            inner: Arc::new(HttpClient::new(url.clone())),
+            url,
+            policy,
+            confirmations,
+        }
+    }
+
+    pub fn url(&self) -> &url::Url {
+        &self.url
+    }
+
+    /// Calls a JSON-RPC method with retries.
+    ///
+    /// An attempt which is timed out is dropped, the request of it is cancelled.
+    pub async fn call<T>(
+        &self,
+        method: &'static str,
+        block: Option<u64>,
+        params: serde_json::Value,
+    ) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        retry::retry(self.policy, method, block, || {
+            self.inner.request(method, params.clone())
         })
-        .and_then(move |(hash, address)| {
-            cli2.get_cellbase_output_capacity_details(hash)
-                .map(move |reward| {
-                    let primary: u64 = reward.primary.into();
-                    (address, primary)
-                })
+        .await
+        .map_err(Into::into)
+    }
+
+    pub async fn tip_header(&self) -> Result<core::HeaderView> {
+        self.call::<rpc::HeaderView>("get_tip_header", None, serde_json::json!([]))
+            .await
+            .map(Into::into)
+    }
+
+    pub async fn header_by_number(&self, number: u64) -> Result<core::HeaderView> {
+        let params = serde_json::json!([format!("{:#x}", number)]);
+        self.call::<rpc::HeaderView>("get_header_by_number", Some(number), params)
+            .await
+            .map(Into::into)
+    }
+
+    async fn epoch_by_number(&self, number: u64) -> Result<rpc::EpochView> {
+        let params = serde_json::json!([format!("{:#x}", number)]);
+        self.call("get_epoch_by_number", None, params).await
+    }
+
+    /// Fetches an epoch, its duration is measured by the first block of the next epoch.
+    pub async fn epoch(&self, number: u64) -> Result<target::Epoch> {
+        let epoch = self.epoch_by_number(number).await?;
+        let start_number: u64 = epoch.start_number.into();
+        let length: u64 = epoch.length.into();
+        let start = self.header_by_number(start_number).await?;
+        let end = self.header_by_number(start_number + length).await?;
+        let duration = end.timestamp().saturating_sub(start.timestamp());
+        Ok(target::Epoch::new(
+            number,
+            epoch.compact_target.into(),
+            length,
+            duration,
+        ))
+    }
+
+    pub async fn epoch_start_number(&self, number: u64) -> Result<u64> {
+        self.epoch_by_number(number)
+            .await
+            .map(|epoch| epoch.start_number.into())
+    }
+
+    /// Fetches the lock of the miner and the reward for a block.
+    ///
+    /// The reward is only available in the cellbase of the block which is `confirmations` later.
+    pub async fn block_record(&self, num_block: u64) -> Result<BlockRecord> {
+        let num_reward = num_block + self.confirmations;
+        let block: core::BlockView = self
+            .call::<rpc::BlockView>(
+                "get_block_by_number",
+                Some(num_block),
+                serde_json::json!([format!("{:#x}", num_block)]),
+            )
+            .await?
+            .into();
+        let lock = cellbase_lock(&block)
+            .map_err(|err| RpcError::new("get_block_by_number", Some(num_block), err))?;
+        let reward_hash = self.header_by_number(num_reward).await?.hash();
+        let reward = self
+            .call::<rpc::BlockReward>(
+                "get_cellbase_output_capacity_details",
+                Some(num_reward),
+                serde_json::json!([format!("{:#x}", reward_hash)]),
+            )
+            .await?;
+        Ok(BlockRecord {
+            number: num_block,
+            hash: block.hash(),
+            parent_hash: block.header().parent_hash(),
+            reward_hash,
+            lock,
+            reward: BlockReward::new(
+                Token::from_shannons(reward.primary.into()),
+                Token::from_shannons(reward.secondary.into()),
+                Token::from_shannons(reward.proposal_reward.into()),
+                Token::from_shannons(reward.tx_fee.into()),
+            ),
         })
-        .then(move |result| {
-            match result {
-                Ok((address, reward)) => {
-                    log::trace!(
-                        "        block {}: reward {}, address: {}",
-                        num_block,
-                        reward,
-                        faster_hex::hex_string(&address).unwrap(),
-                    );
-                    rec.write().insert(num_block, (address, reward));
+    }
+}
+
+fn cellbase_lock(block: &core::BlockView) -> result::Result<packed::Script, InvalidCellbase> {
+    let cellbase = block
+        .transaction(0)
+        .ok_or_else(|| InvalidCellbase("no transactions".to_owned()))?;
+    let witness = cellbase
+        .witnesses()
+        .get(0)
+        .ok_or_else(|| InvalidCellbase("no witnesses".to_owned()))?
+        .raw_data();
+    let lock = packed::CellbaseWitnessReader::from_slice(&witness)
+        .map_err(|err| InvalidCellbase(err.to_string()))?
+        .lock()
+        .to_entity();
+    Ok(lock)
+}
+
+/// Reports the progress of fetching, with the speed and the estimated remaining time.
+struct Progress {
+    started: time::Instant,
+    reported: time::Instant,
+    total: u64,
+    done: u64,
+}
+
+impl Progress {
+    fn new(total: u64) -> Self {
+        let now = time::Instant::now();
+        Self {
+            started: now,
+            reported: now,
+            total,
+            done: 0,
+        }
+    }
+
+    fn tick(&mut self) {
+        self.done += 1;
+        let now = time::Instant::now();
+        if self.done < self.total
+            && now.duration_since(self.reported).as_secs() < constants::PROGRESS_INTERVAL_SECS
+        {
+            return;
+        }
+        self.reported = now;
+        let elapsed = now.duration_since(self.started).as_millis() as f64 / 1000.0;
+        let speed = if elapsed > 0.0 {
+            self.done as f64 / elapsed
+        } else {
+            0.0
+        };
+        let eta = if speed > 0.0 {
+            ((self.total - self.done) as f64 / speed).ceil() as u64
+        } else {
+            0
+        };
+        log::info!(
+            "    fetched {}/{} blocks, {:.1} blocks/s, ETA {}s",
+            self.done,
+            self.total,
+            speed,
+            eta
+        );
+    }
+}
+
+/// Fetches the records of blocks in order, each block is fetched exactly once.
+///
+/// At most `concurrency` blocks are in flight, and the requests are throttled by `rate`
+/// requests per second, `0` means no limit.
+struct Fetcher {
+    client: Client,
+    concurrency: usize,
+    rate: u32,
+    next: u64,
+    records: BTreeMap<u64, BlockRecord>,
+}
+
+impl Fetcher {
+    fn new(args: &arguments::RpcArguments, client: Client, start: u64) -> Self {
+        Self {
+            client,
+            concurrency: args.concurrency(),
+            rate: args.rate(),
+            next: start,
+            records: BTreeMap::new(),
+        }
+    }
+
+    /// Fetches all blocks which are not fetched yet, up to `end` (inclusive).
+    async fn fetch_until(&mut self, end: u64) -> Result<()> {
+        if end < self.next {
+            return Ok(());
+        }
+        let start = self.next;
+        log::info!("    fetching blocks [{}, {}] ...", start, end);
+        let period = if self.rate == 0 {
+            None
+        } else {
+            Some(time::Duration::from_secs(constants::REQUESTS_PER_BLOCK) / self.rate)
+        };
+        let started = tokio::time::Instant::now();
+        let client = &self.client;
+        let mut fetched = stream::iter(start..=end)
+            .map(|number| async move {
+                if let Some(period) = period {
+                    let index = (number - start) as u32;
+                    tokio::time::sleep_until(started + period * index).await;
                 }
-                Err(err) => {
-                    log::trace!("    error for block {}: {:?}", num_block, err);
+                client.block_record(number).await
+            })
+            .buffered(self.concurrency);
+        let mut progress = Progress::new(end - start + 1);
+        while let Some(result) = fetched.next().await {
+            let record = result?;
+            progress.tick();
+            if record.number != self.next {
+                return Err(Error::Unreachable(format!(
+                    "block {} is fetched out of order, expect {}",
+                    record.number, self.next
+                )));
+            }
+            if log::log_enabled!(log::Level::Trace) {
+                log::trace!(
+                    "        block {}: reward ({}), address: {}",
+                    record.number,
+                    record.reward,
+                    faster_hex::hex_string(&record.lock.args().raw_data()).unwrap_or_default(),
+                );
+            }
+            self.records.insert(record.number, record);
+            self.next += 1;
+        }
+        Ok(())
+    }
+
+    fn record(&self, number: u64) -> Result<&BlockRecord> {
+        self.records
+            .get(&number)
+            .ok_or_else(|| Error::Unreachable(format!("block {} is not fetched", number)))
+    }
+
+    /// Re-verifies that all records are on the canonical chain, from the block `end` back to
+    /// the block `start`, and refetches the blocks which were reorganized out.
+    async fn verify(&mut self, start: u64, end: u64) -> Result<()> {
+        log::info!(
+            "    verifying blocks [{}, {}] on the canonical chain ...",
+            start,
+            end
+        );
+        for round in 1..=constants::REORG_CHECK_ROUNDS {
+            // The blocks which are after `end` only have rewards for the last blocks.
+            let mut tail = HashMap::new();
+            for number in (end + 1)..=(end + self.client.confirmations) {
+                tail.insert(number, self.client.header_by_number(number).await?.hash());
+            }
+            let mut expected = self.client.header_by_number(end).await?.hash();
+            let mut reorged = 0;
+            for number in (start..=end).rev() {
+                let num_reward = number + self.client.confirmations;
+                let expected_reward = if let Some(hash) = tail.get(&num_reward) {
+                    hash.clone()
+                } else {
+                    self.record(num_reward)?.hash.clone()
+                };
+                let record = self.record(number)?;
+                if record.hash != expected || record.reward_hash != expected_reward {
+                    log::warn!("    block {} was reorganized, fetch it again", number);
+                    let record = self.client.block_record(number).await?;
+                    self.records.insert(number, record);
+                    reorged += 1;
                 }
-            };
-            {
-                *cnt.write() += 1;
+                expected = self.record(number)?.parent_hash.clone();
             }
-            Ok(())
-        });
-    rt.spawn(fut);
+            if expected != self.client.header_by_number(start - 1).await?.hash() {
+                let err = ChainError::Disconnected(start);
+                return Err(RpcError::new("get_header_by_number", Some(start - 1), err).into());
+            }
+            if reorged == 0 {
+                log::info!(
+                    "    all {} blocks are on the canonical chain",
+                    end - start + 1
+                );
+                return Ok(());
+            }
+            log::warn!(
+                "    {} blocks were reorganized in round {}, verify again ...",
+                reorged,
+                round
+            );
+        }
+        let err = ChainError::Unstable(constants::REORG_CHECK_ROUNDS);
+        Err(RpcError::new("get_header_by_number", None, err).into())
+    }
 }
 
+/// Runs the future until it is done, it could be cancelled by Ctrl-C.
+fn run<T, F: Future<Output = Result<T>>>(fut: F) -> Result<T> {
+    let rt = runtime::Builder::new_multi_thread().enable_all().build()?;
+    let result = rt.block_on(async {
+        tokio::select! {
+            result = fut => result,
+            result = signal::ctrl_c() => {
+                result?;
+                log::warn!("interrupted, cancel all requests ...");
+                Err(Error::Interrupted)
+            }
+        }
+    });
+    // Do not wait for the address lookups which are still running.
+    rt.shutdown_background();
+    result
+}
+
+/// Fetches the chain data, after the specified epoch is reached.
 pub fn fetch(args: &arguments::Arguments) -> Result<ChainData> {
-    let records = Arc::new(RwLock::new(HashMap::new()));
-    let count = Arc::new(RwLock::new(0));
+    run(fetch_chain_data(args))
+}
 
-    let mut rt = runtime::Builder::new().blocking_threads(4).build().unwrap();
-    let cli_async = Arc::new(CkbAsyncClient::new(args.url().to_owned()));
-    let cli_sync = CkbSyncClient::new(args.url().to_owned());
+/// Fetches the mined blocks in the range `[from, to]`.
+pub fn fetch_blocks(
+    args: &arguments::RpcArguments,
+    chain: &ChainConfig,
+    from: u64,
+    to: u64,
+) -> Result<BTreeMap<u64, MinedBlock>> {
+    run(fetch_blocks_in_range(args, chain, from, to))
+}
 
-    let mut number_start = 1 + constants::CONFIRMATIONS;
-    let batch_size = 512;
+/// Fetches the epochs, all of them should be finished.
+pub fn fetch_epochs(
+    args: &arguments::RpcArguments,
+    chain: &ChainConfig,
+    numbers: &[u64],
+) -> Result<Vec<target::Epoch>> {
+    run(fetch_epochs_by_numbers(args, chain, numbers))
+}
+
+fn build_clients(args: &arguments::RpcArguments, chain: &ChainConfig) -> Vec<Client> {
+    let policy = args.retry().to_owned();
+    args.urls()
+        .iter()
+        .map(|url| Client::new(url.to_owned(), policy, chain.consensus.confirmations))
+        .collect()
+}
+
+async fn fetch_blocks_in_range(
+    args: &arguments::RpcArguments,
+    chain: &ChainConfig,
+    from: u64,
+    to: u64,
+) -> Result<BTreeMap<u64, MinedBlock>> {
+    let clients = build_clients(args, chain);
+    let tip_number = clients[0].tip_header().await?.number();
+    if to + chain.consensus.confirmations > tip_number {
+        let err = ChainError::Unavailable(to, tip_number);
+        return Err(RpcError::new("get_tip_header", None, err).into());
+    }
+    let fetchers = clients
+        .iter()
+        .map(|client| Fetcher::new(args, client.to_owned(), from))
+        .collect();
+    let (blocks, _, _) = agree(
+        args,
+        &clients,
+        fetchers,
+        "get_block_by_number",
+        from,
+        to,
+        &[],
+    )
+    .await?;
+    Ok(blocks)
+}
+
+async fn fetch_epochs_by_numbers(
+    args: &arguments::RpcArguments,
+    chain: &ChainConfig,
+    numbers: &[u64],
+) -> Result<Vec<target::Epoch>> {
+    let clients = build_clients(args, chain);
+    let tip_epoch = clients[0].tip_header().await?.epoch().number();
+    if let Some(last) = numbers.iter().max() {
+        if *last >= tip_epoch {
+            let err = ChainError::Unfinished(*last, tip_epoch);
+            return Err(RpcError::new("get_tip_header", None, err).into());
+        }
+    }
+    call_all(
+        args,
+        &clients,
+        "get_epoch_by_number",
+        None,
+        |client| async move {
+            let mut epochs = Vec::with_capacity(numbers.len());
+            for number in numbers {
+                epochs.push(client.epoch(*number).await?);
+            }
+            Ok::<_, Error>(epochs)
+        },
+    )
+    .await
+}
+
+/// Calls all endpoints, then chooses the value which the quorum agrees on.
+async fn call_all<'a, T, F, Fut>(
+    args: &arguments::RpcArguments,
+    clients: &'a [Client],
+    method: &str,
+    block: Option<u64>,
+    func: F,
+) -> Result<T>
+where
+    T: PartialEq,
+    F: Fn(&'a Client) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let results = future::join_all(clients.iter().map(func)).await;
+    let total = results.len();
+    let mut values = Vec::with_capacity(total);
+    for (client, result) in clients.iter().zip(results) {
+        match result {
+            Ok(value) => values.push((client.url(), value)),
+            Err(err) => log::error!("    endpoint {} is unavailable: {}", client.url(), err),
+        }
+    }
+    if values.len() < args.quorum() {
+        let err = ChainError::NoQuorum(values.len(), total, args.quorum());
+        return Err(RpcError::new(method, block, err).into());
+    }
+    consensus(args.quorum(), method, block, values)
+}
+
+async fn fetch_chain_data(args: &arguments::Arguments) -> Result<ChainData> {
+    let confirmations = args.chain().consensus.confirmations;
+    let clients = build_clients(args.rpc(), args.chain());
+    // The tip of the first endpoint decides when the epoch is reached, but all endpoints are
+    // synced while waiting, so none of them starts from the genesis at the end.
+    let client = &clients[0];
+    let mut fetchers = clients
+        .iter()
+        .map(|client| Fetcher::new(args.rpc(), client.to_owned(), 1))
+        .collect::<Vec<_>>();
 
     log::info!(
         "waiting the specified epoch {} and syncing chain data ...",
         args.epoch()
     );
     loop {
-        let tip_header = cli_sync.tip_header().expect("failed to fetch tip header");
+        let tip_header = client.tip_header().await?;
         let tip_number = tip_header.number();
         let tip_epoch = tip_header.epoch();
         if tip_epoch.number() < args.epoch()
-            || (tip_epoch.number() == args.epoch()
-                && tip_epoch.index() < constants::CONFIRMATIONS - 1)
+            || (tip_epoch.number() == args.epoch() && tip_epoch.index() < confirmations - 1)
         {
-            let syncing_batch = if tip_number > number_start {
-                let mut number_end = number_start + batch_size;
-                let syncing_batch = if number_end > tip_number {
-                    number_end = tip_number;
-                    false
-                } else {
-                    true
-                };
-                for num in (number_start..=number_end).into_iter() {
-                    let cli = Arc::clone(&cli_async);
-                    let cnt = Arc::clone(&count);
-                    let rec = Arc::clone(&records);
-                    fetch_for_number(num, &mut rt, cli, cnt, rec);
+            // The reward of a block is only available after `confirmations` blocks.
+            if tip_number > confirmations {
+                let end = tip_number - confirmations;
+                let results =
+                    future::join_all(fetchers.iter_mut().map(|fetcher| fetcher.fetch_until(end)))
+                        .await;
+                // An endpoint which falls behind catches up later, the quorum is checked at last.
+                for (client, result) in clients.iter().zip(results) {
+                    if let Err(err) = result {
+                        log::warn!("    endpoint {} falls behind: {}", client.url(), err);
+                    }
                 }
-                number_start += number_end + 1;
-                syncing_batch
-            } else {
-                false
-            };
+            }
             let is_almost_finished = tip_epoch.number() < args.epoch() - 1
                 || tip_epoch.length() - tip_epoch.index() > 16;
 
-            let wait_millis = if is_almost_finished || syncing_batch {
-                2000
-            } else {
-                60 * 1000
-            };
+            let wait_millis = if is_almost_finished { 2000 } else { 60 * 1000 };
             log::info!(
                 "    number: {}, epoch: {:#}, waiting {} ms for epoch {}({}/--) ...",
                 tip_number,
                 tip_epoch,
                 wait_millis,
                 args.epoch(),
-                constants::CONFIRMATIONS - 1,
+                confirmations - 1,
             );
-            sleep_millis(wait_millis);
+            tokio::time::sleep(time::Duration::from_millis(wait_millis)).await;
         } else {
             log::info!(
                 "done: expect epoch {}({}/--), and current is {:#}",
                 args.epoch(),
-                constants::CONFIRMATIONS - 1,
+                confirmations - 1,
                 tip_epoch,
             );
             break;
@@ -165,130 +612,470 @@ pub fn fetch(args: &arguments::Arguments) -> Result<ChainData> {
     }
 
     log::info!("epoch is reached, only syncing chain data ...");
-    let number_last = {
-        let tmp: u64 = cli_sync
-            .epoch_by_number(args.epoch())
-            .expect("failed to fetch tip header")
-            .start_number
-            .into();
-        tmp - 1
+    // The last block is before the start of the epoch, the quorum should agree on it.
+    let epoch_start = call_all(
+        args.rpc(),
+        &clients,
+        "get_epoch_by_number",
+        None,
+        |client| client.epoch_start_number(args.epoch()),
+    )
+    .await?;
+    let number_last = epoch_start - 1;
+    let numbers = (1..=args.target().window())
+        .rev()
+        .map(|i| args.epoch() - i)
+        .collect::<Vec<_>>();
+    let (blocks, header, epochs) = agree(
+        args.rpc(),
+        &clients,
+        fetchers,
+        "get_block_by_number",
+        1,
+        number_last,
+        &numbers,
+    )
+    .await?;
+
+    let chain_data = ChainData {
+        blocks,
+        header,
+        epochs,
     };
-    let number_stop = number_last + constants::CONFIRMATIONS;
-    loop {
-        if number_stop >= number_start {
-            let mut number_end = number_start + batch_size;
-            if number_end > number_stop {
-                number_end = number_stop;
-            }
-            for num in (number_start..=number_end).into_iter() {
-                let cli = Arc::clone(&cli_async);
-                let cnt = Arc::clone(&count);
-                let rec = Arc::clone(&records);
-                fetch_for_number(num, &mut rt, cli, cnt, rec);
+    if log::log_enabled!(log::Level::Info) {
+        log::info!("aggregate rewards for each miner ...");
+        for (lock, reward) in chain_data.miners()?.iter() {
+            log::info!(
+                "        lock {}: primary {}, total {}",
+                lock,
+                reward.primary(),
+                reward.total()?
+            );
+        }
+    }
+    Ok(chain_data)
+}
+
+/// Fetches blocks `[start, end]`, the header of `end` and the epochs `numbers` from
+/// all endpoints, then chooses the data which the quorum agrees on.
+///
+/// The `method` is reported when too few endpoints are available.
+async fn agree(
+    args: &arguments::RpcArguments,
+    clients: &[Client],
+    fetchers: Vec<Fetcher>,
+    method: &str,
+    start: u64,
+    end: u64,
+    numbers: &[u64],
+) -> Result<(
+    BTreeMap<u64, MinedBlock>,
+    core::HeaderView,
+    Vec<target::Epoch>,
+)> {
+    let results = future::join_all(
+        fetchers
+            .into_iter()
+            .map(|fetcher| fetch_endpoint(fetcher, start, end, numbers)),
+    )
+    .await;
+    let total = results.len();
+    let mut endpoints = Vec::with_capacity(total);
+    for (client, result) in clients.iter().zip(results) {
+        match result {
+            Ok(endpoint) => endpoints.push(endpoint),
+            Err(err) => log::error!("    endpoint {} is unavailable: {}", client.url(), err),
+        }
+    }
+    if endpoints.len() < args.quorum() {
+        let err = ChainError::NoQuorum(endpoints.len(), total, args.quorum());
+        return Err(RpcError::new(method, Some(end), err).into());
+    }
+
+    log::info!(
+        "check consensus of {} endpoints (quorum is {}) ...",
+        endpoints.len(),
+        args.quorum()
+    );
+    let mut blocks = BTreeMap::new();
+    for idx in start..=end {
+        let values = endpoints
+            .iter()
+            .map(|endpoint| {
+                let url = endpoint.fetcher.client.url();
+                endpoint.fetcher.record(idx).map(|record| (url, record))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let record = consensus(args.quorum(), "get_block_by_number", Some(idx), values)?;
+        let block = MinedBlock {
+            lock: record.lock.clone(),
+            reward: record.reward,
+        };
+        blocks.insert(idx, block);
+    }
+    let header = {
+        let values = endpoints
+            .iter()
+            .map(|endpoint| (endpoint.fetcher.client.url(), endpoint.header.hash()))
+            .collect();
+        let hash = consensus(args.quorum(), "get_header_by_number", Some(end), values)?;
+        endpoints
+            .iter()
+            .find(|endpoint| endpoint.header.hash() == hash)
+            .map(|endpoint| endpoint.header.clone())
+            .ok_or_else(|| Error::Unreachable("no header for the consensus hash".to_owned()))?
+    };
+    let epochs = {
+        let values = endpoints
+            .iter()
+            .map(|endpoint| (endpoint.fetcher.client.url(), &endpoint.epochs))
+            .collect();
+        consensus(args.quorum(), "get_epoch_by_number", None, values)?.to_owned()
+    };
+    Ok((blocks, header, epochs))
+}
+
+impl MinedBlock {
+    pub fn miner(&self) -> config::Lock {
+        config::Lock::from_script(&self.lock)
+    }
+}
+
+impl ChainData {
+    /// The SHA-256 of all chain data which the genesis depends on: the last header, the miner
+    /// and the reward of each block, and the sampled epochs.
+    pub fn digest(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.input(self.header.hash().as_slice());
+        for (number, block) in &self.blocks {
+            hasher.input(&number.to_le_bytes());
+            hasher.input(block.lock.as_slice());
+            for component in &[
+                block.reward.primary(),
+                block.reward.secondary(),
+                block.reward.proposal(),
+                block.reward.tx_fee(),
+            ] {
+                hasher.input(&component.shannons().to_le_bytes());
             }
-            number_start = number_end + 1;
-            sleep_millis(2000);
+        }
+        for epoch in &self.epochs {
+            hasher.input(&epoch.number().to_le_bytes());
+            hasher.input(&epoch.compact_target().to_le_bytes());
+            hasher.input(&epoch.length().to_le_bytes());
+            hasher.input(&epoch.duration().to_le_bytes());
+        }
+        faster_hex::hex_string(&hasher.result()).unwrap_or_default()
+    }
+
+    /// The full rewards of each miner, by the full lock script.
+    pub fn miners(&self) -> Result<HashMap<config::Lock, BlockReward>> {
+        let mut miners = HashMap::new();
+        for block in self.blocks.values() {
+            let reward = miners
+                .entry(block.miner())
+                .or_insert_with(BlockReward::default);
+            *reward = reward.checked_add(&block.reward)?;
+        }
+        Ok(miners)
+    }
+
+    /// The rewards of each miner, only the chosen components are counted.
+    pub fn rewards(&self, components: &[Component]) -> Result<HashMap<config::Lock, Token>> {
+        self.miners()?
+            .into_iter()
+            .map(|(address, reward)| reward.sum_of(components).map(|token| (address, token)))
+            .collect()
+    }
+}
+
+/// Fetches all the chain data which is required from one endpoint.
+async fn fetch_endpoint(
+    mut fetcher: Fetcher,
+    start: u64,
+    end: u64,
+    numbers: &[u64],
+) -> Result<Endpoint> {
+    log::info!("    fetching from endpoint {} ...", fetcher.client.url());
+    fetcher.fetch_until(end).await?;
+    fetcher.verify(start, end).await?;
+    let header = fetcher.client.header_by_number(end).await?;
+    let mut epochs = Vec::with_capacity(numbers.len());
+    for number in numbers {
+        let epoch = fetcher.client.epoch(*number).await?;
+        epochs.push(epoch);
+    }
+    Ok(Endpoint {
+        fetcher,
+        header,
+        epochs,
+    })
+}
+
+/// Chooses the value which most endpoints agree on, at least `quorum` endpoints are required.
+///
+/// All endpoints which disagree with the chosen value are reported.
+fn consensus<T: PartialEq>(
+    quorum: usize,
+    method: &str,
+    block: Option<u64>,
+    values: Vec<(&url::Url, T)>,
+) -> Result<T> {
+    let total = values.len();
+    let mut groups: Vec<(T, Vec<&url::Url>)> = Vec::new();
+    for (url, value) in values {
+        if let Some(group) = groups.iter_mut().find(|group| group.0 == value) {
+            group.1.push(url);
         } else {
-            break;
+            groups.push((value, vec![url]));
+        }
+    }
+    groups.sort_by(|a, b| b.1.len().cmp(&a.1.len()));
+    let mut groups = groups.into_iter();
+    let (value, agreed) = groups
+        .next()
+        .ok_or_else(|| Error::Unreachable("no endpoints for consensus".to_owned()))?;
+    for (_, urls) in groups {
+        for url in urls {
+            let block_str = block.map(|n| format!(" for block {}", n));
+            log::warn!(
+                "    divergence: '{}'{} from {} disagrees with {} endpoints",
+                method,
+                block_str.unwrap_or_default(),
+                url,
+                agreed.len()
+            );
         }
     }
+    if agreed.len() < quorum {
+        let err = ChainError::NoQuorum(agreed.len(), total, quorum);
+        Err(RpcError::new(method, block, err).into())
+    } else {
+        Ok(value)
+    }
+}
 
-    log::info!("syncing is done, waiting for all results ...");
-    loop {
-        let count = { *count.read() };
-        log::trace!("    will stop at {}, current {}", number_last, count);
-        if count == number_last {
-            let data_count = { records.read().len() } as u64;
-            if count > data_count {
-                log::warn!("    require {} records, but only get {}", count, data_count);
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use hyper::{
+        body,
+        service::{make_service_fn, service_fn},
+        Body, Response, Server,
+    };
+
+    use super::*;
+
+    /// A reply of the mock server, after the delay.
+    #[derive(Clone)]
+    struct Reply {
+        delay: time::Duration,
+        status: u16,
+        result: Option<&'static str>,
+        error: Option<(i64, &'static str)>,
+    }
+
+    impl Reply {
+        fn ok(result: &'static str) -> Self {
+            Self {
+                delay: time::Duration::from_millis(0),
+                status: 200,
+                result: Some(result),
+                error: None,
             }
-            break;
-        } else if count > number_last {
-            return Err(Error::Unreachable(
-                "count > number_last when syncing".to_owned(),
-            ));
         }
-        sleep_millis(500);
-    }
 
-    log::info!("aggregate round 5.3 mined ...");
-    let mut rewards = HashMap::new();
-    {
-        let records = records.read();
-        for idx in 1..=number_last {
-            let (address, reward) = records.get(&idx).cloned().unwrap_or_else(|| {
-                cli_sync
-                    .block_by_number(idx)
-                    .map(|block| {
-                        let cellbase_input = block
-                            .transaction(0)
-                            .unwrap()
-                            .witnesses()
-                            .get(0)
-                            .unwrap()
-                            .raw_data();
-                        packed::CellbaseWitnessReader::from_slice(&cellbase_input)
-                            .unwrap()
-                            .lock()
-                            .args()
-                            .raw_data()
-                            .to_owned()
-                    })
-                    .and_then(|address| {
-                        cli_sync
-                            .block_hash(Some(idx + constants::CONFIRMATIONS))
-                            .map(move |hash| (hash, address))
-                    })
-                    .and_then(|(hash, address)| {
-                        cli_sync
-                            .get_cellbase_output_capacity_details(hash)
-                            .map(move |reward| {
-                                let primary: u64 = reward.primary.into();
-                                (address, primary)
-                            })
-                    })
-                    .unwrap()
-            });
-            if log::log_enabled!(log::Level::Trace) {
-                log::trace!(
-                    "        block {}: {}, {}",
-                    idx,
-                    faster_hex::hex_string(&address).unwrap(),
-                    reward
-                );
+        fn not_found() -> Self {
+            Self {
+                result: None,
+                ..Self::ok("")
             }
-            {
-                let total_reward = rewards.entry(address).or_insert(0);
-                *total_reward += reward;
+        }
+
+        fn error(code: i64, message: &'static str) -> Self {
+            Self {
+                result: None,
+                error: Some((code, message)),
+                ..Self::ok("")
             }
         }
-        if log::log_enabled!(log::Level::Info) {
-            for (address, reward) in rewards.iter() {
-                log::info!(
-                    "        address {}: {}",
-                    faster_hex::hex_string(&address).unwrap(),
-                    reward
-                );
+
+        fn failed() -> Self {
+            Self {
+                status: 500,
+                ..Self::ok("")
+            }
+        }
+
+        fn delayed(self, millis: u64) -> Self {
+            Self {
+                delay: time::Duration::from_millis(millis),
+                ..self
             }
         }
     }
 
-    let header = cli_sync.header_by_number(number_last).unwrap();
+    /// Starts a JSON-RPC server which replies the requests in order, the last reply is
+    /// repeated; the count of received requests is returned with its URL.
+    fn mock(replies: Vec<Reply>) -> (url::Url, Arc<AtomicUsize>) {
+        let replies = Arc::new(replies);
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&count);
+        let make_service = make_service_fn(move |_| {
+            let replies = Arc::clone(&replies);
+            let counter = Arc::clone(&counter);
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: hyper::Request<Body>| {
+                    let index = counter.fetch_add(1, Ordering::SeqCst);
+                    let reply = replies[index.min(replies.len() - 1)].clone();
+                    async move {
+                        let content = body::to_bytes(request.into_body()).await.unwrap();
+                        let request: serde_json::Value = serde_json::from_slice(&content).unwrap();
+                        tokio::time::sleep(reply.delay).await;
+                        let content = if let Some((code, message)) = reply.error {
+                            serde_json::json!({
+                                "jsonrpc": "2.0",
+                                "id": request["id"],
+                                "error": { "code": code, "message": message },
+                            })
+                        } else {
+                            serde_json::json!({
+                                "jsonrpc": "2.0",
+                                "id": request["id"],
+                                "result": reply.result,
+                            })
+                        };
+                        let response = Response::builder()
+                            .status(reply.status)
+                            .body(Body::from(content.to_string()))
+                            .unwrap();
+                        Ok::<_, Infallible>(response)
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let url = url::Url::parse(&format!("http://{}", server.local_addr())).unwrap();
+        tokio::spawn(server);
+        (url, count)
+    }
 
-    let diff_avg = (1..=constants::EPOCH_AVG_COUNT)
-        .map(|i| {
-            let target = cli_sync
-                .epoch_by_number(args.epoch() - i)
-                .unwrap()
-                .compact_target;
-            utilities::compact_to_difficulty(target.into())
-        })
-        .sum::<U256>()
-        / U256::from(constants::EPOCH_AVG_COUNT);
+    fn client(url: url::Url, max_attempts: u32, timeout_millis: u64) -> Client {
+        let timeout = time::Duration::from_millis(timeout_millis);
+        Client::new(url, retry::RetryPolicy::new(max_attempts, timeout), 0)
+    }
 
-    Ok(ChainData {
-        rewards,
-        header,
-        diff_avg,
-    })
+    async fn tip(client: &Client) -> Result<String> {
+        client
+            .call("get_tip_block_number", None, serde_json::json!([]))
+            .await
+    }
+
+    #[tokio::test]
+    async fn retry_until_succeeded() {
+        let (url, count) = mock(vec![Reply::failed(), Reply::failed(), Reply::ok("0x10")]);
+        let client = client(url, 3, 1000);
+        assert_eq!(tip(&client).await.unwrap(), "0x10");
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn give_up_after_max_attempts() {
+        let (url, count) = mock(vec![Reply::failed()]);
+        let client = client(url, 2, 1000);
+        match tip(&client).await {
+            Err(Error::Rpc(err)) => assert_eq!(err.method(), "get_tip_block_number"),
+            _ => panic!("should fail after 2 attempts"),
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn replied_errors_are_not_retried() {
+        let (url, count) = mock(vec![
+            Reply::error(-32601, "Method not found"),
+            Reply::ok("0x1"),
+        ]);
+        let client = client(url, 3, 1000);
+        match tip(&client).await {
+            Err(Error::Rpc(err)) => assert_eq!(err.method(), "get_tip_block_number"),
+            _ => panic!("should fail without retries"),
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        let (url, count) = mock(vec![Reply::not_found(), Reply::ok("0x1")]);
+        let client = client(url, 3, 1000);
+        assert!(tip(&client).await.is_err());
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn timed_out_attempts_are_retried() {
+        let (url, count) = mock(vec![
+            Reply::ok("0x1").delayed(5000),
+            Reply::ok("0x2").delayed(5000),
+            Reply::ok("0x3"),
+        ]);
+        let client = client(url, 3, 200);
+        let started = time::Instant::now();
+        assert_eq!(tip(&client).await.unwrap(), "0x3");
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        // The timed out requests are cancelled, they are not waited for.
+        assert!(started.elapsed() < time::Duration::from_millis(5000));
+    }
+
+    #[tokio::test]
+    async fn consensus_of_endpoints() {
+        let clients = vec![
+            mock(vec![Reply::ok("0xa")]),
+            mock(vec![Reply::ok("0xb")]),
+            mock(vec![Reply::ok("0xa")]),
+            mock(vec![Reply::failed()]),
+        ]
+        .into_iter()
+        .map(|(url, _)| client(url, 1, 1000))
+        .collect::<Vec<_>>();
+        let mut values = Vec::new();
+        for client in &clients {
+            if let Ok(value) = tip(client).await {
+                values.push((client.url(), value));
+            }
+        }
+        assert_eq!(values.len(), 3);
+        let agreed = consensus(2, "get_tip_block_number", None, values.clone()).unwrap();
+        assert_eq!(agreed, "0xa");
+        match consensus(3, "get_tip_block_number", None, values) {
+            Err(Error::Rpc(err)) => assert_eq!(err.method(), "get_tip_block_number"),
+            _ => panic!("should have no quorum"),
+        }
+    }
+
+    #[test]
+    fn consensus_by_majority() {
+        let urls = (0..4)
+            .map(|i| url::Url::parse(&format!("http://127.0.0.1:{}", 8114 + i)).unwrap())
+            .collect::<Vec<_>>();
+        let values = |numbers: &[u64]| -> Vec<(&url::Url, u64)> {
+            urls.iter().zip(numbers.iter().cloned()).collect()
+        };
+        assert_eq!(consensus(1, "m", Some(1), values(&[7])).unwrap(), 7);
+        assert_eq!(
+            consensus(4, "m", Some(1), values(&[7, 7, 7, 7])).unwrap(),
+            7
+        );
+        assert_eq!(
+            consensus(3, "m", Some(1), values(&[8, 7, 7, 7])).unwrap(),
+            7
+        );
+        assert!(consensus(3, "m", Some(1), values(&[8, 8, 7, 7])).is_err());
+        assert!(consensus(2, "m", Some(1), values(&[5, 6, 7, 8])).is_err());
+        match consensus::<u64>(1, "m", Some(1), Vec::new()) {
+            Err(Error::Unreachable(_)) => {}
+            _ => panic!("should have no endpoints"),
+        }
+    }
 }
@@ -6,22 +6,11 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-pub const SECP256K1_BLAKE160_SIGHASH_CODE_HASH: &str =
-    "0x9bd7e06f3ecf4be0f2fcd2188b23f1b9fcc88e5d4b65a8637b17723bbda3cce8";
-pub const SECP256K1_BLAKE160_MULTISIG_CODE_HASH: &str =
-    "0x5c5069eb0857efc65e1bca0c07df34c31663b3622fd3876c876320fc9634e2a8";
+// The chain constants are in the chain configs, see `module::chain`.
 
-pub const FOUNDATION_ADDR: &str = "ckb1qyqyz340d4nhgtx2s75mp5wnavrsu7j5fcwqktprrp";
-pub const FOUNDATION_SINCE: &str = "2020-07-01";
+// The z-score of the 95% confidence intervals of the estimations.
+pub const CONFIDENCE_Z: f64 = 1.96;
 
-pub const FOUNDATION_TESTNET_ADDR: &str = "ckb1qyqy6mtud5sgctjwgg6gydd0ea05mr339lnslczzrc";
-
-pub const SATOSHI_GIFT_CODE_HASH: &str =
-    "0x0000000000000000000000000000000000000000000000000000000000000000";
-pub const SATOSHI_GIFT_ARGS: &str = "0x62e907b15cbf27d5425399ebf6f0fb50ebb88f18";
-
-pub const CONFIRMATIONS: u64 = 11;
-pub const INITIAL_TOTAL_SUPPLY: u64 = 3_360_000_000_000_000_000;
-
-pub const EPOCH_AVG_COUNT: u64 = 4;
-pub const PLANNED_EPOCH: u64 = 90;
+pub const REQUESTS_PER_BLOCK: u64 = 3;
+pub const PROGRESS_INTERVAL_SECS: u64 = 5;
+pub const REORG_CHECK_ROUNDS: u32 = 3;
@@ -6,7 +6,28 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-pub const SPECS_TEMPLATE: &str = include_str!("specs.toml");
+/// The base specs which the issued cells and the chain parameters are filled into.
+pub const SPECS_BASE: &str = include_str!("specs-base.toml");
+pub const SPECS_DEV_BASE: &str = include_str!("specs-dev-base.toml");
+
+/// The built-in chain configs, by the preset names.
+pub const CHAIN_PRESETS: &[(&str, &str)] = &[
+    ("mainnet-lina", include_str!("chains/mainnet-lina.toml")),
+    ("testnet-aggron", include_str!("chains/testnet-aggron.toml")),
+    ("dev", include_str!("chains/dev.toml")),
+];
+
+pub const ROUND_1_AWARDS_PATH: &str = "competitions/round-1/awards.csv";
+pub const ROUND_1_LOTTERY_PATH: &str = "competitions/round-1/lottery.csv";
+pub const ROUND_2_MINED_PATH: &str = "competitions/round-2/miner_reward_finally.csv";
+pub const ROUND_2_LUCKY_PATH: &str = "competitions/round-2/epoch_reward_finally.csv";
+pub const ROUND_3_MINED_PATH: &str = "competitions/round-3/miner_reward.csv";
+pub const ROUND_3_LUCKY_PATH: &str = "competitions/round-3/epoch_reward.csv";
+pub const ROUND_4_MINED_PATH: &str = "competitions/round-4/miner_reward.csv";
+pub const ROUND_5_S1_MINED_PATH: &str = "competitions/round-5/stage-1/miner_reward.csv";
+pub const ROUND_5_S2_MINED_PATH: &str = "competitions/round-5/stage-2/miner_reward.csv";
+pub const ROUND_5_S3_MINED_PATH: &str = "chain data (round-5.3)";
+pub const GENESIS_ALLOCATE_PATH: &str = "allocate/genesis_final.csv";
 
 pub const ROUND_1_AWARDS: &str = include_str!("competitions/round-1/awards.csv");
 pub const ROUND_1_LOTTERY: &str = include_str!("competitions/round-1/lottery.csv");
@@ -19,3 +40,17 @@ pub const ROUND_5_S1_MINED: &str = include_str!("competitions/round-5/stage-1/mi
 pub const ROUND_5_S2_MINED: &str = include_str!("competitions/round-5/stage-2/miner_reward.csv");
 
 pub const GENESIS_ALLOCATE: &str = include_str!("allocate/genesis_final.csv");
+
+/// All embedded data files, by their paths, they are recorded in the manifest.
+pub const EMBEDDED_FILES: &[(&str, &str)] = &[
+    (ROUND_1_AWARDS_PATH, ROUND_1_AWARDS),
+    (ROUND_1_LOTTERY_PATH, ROUND_1_LOTTERY),
+    (ROUND_2_MINED_PATH, ROUND_2_MINED),
+    (ROUND_2_LUCKY_PATH, ROUND_2_LUCKY),
+    (ROUND_3_MINED_PATH, ROUND_3_MINED),
+    (ROUND_3_LUCKY_PATH, ROUND_3_LUCKY),
+    (ROUND_4_MINED_PATH, ROUND_4_MINED),
+    (ROUND_5_S1_MINED_PATH, ROUND_5_S1_MINED),
+    (ROUND_5_S2_MINED_PATH, ROUND_5_S2_MINED),
+    (GENESIS_ALLOCATE_PATH, GENESIS_ALLOCATE),
+];
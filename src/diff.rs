@@ -0,0 +1,382 @@
+// Copyright (C) 2019 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+    fs, io,
+};
+
+use ckb_chain_spec::ChainSpec;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    arguments,
+    error::{DataError, DataErrorKind, Error, Result},
+    module::{config, token::Token},
+};
+
+/// An issued cell as it is serialized in a spec.
+#[derive(Deserialize)]
+struct SpecCell {
+    capacity: u64,
+    lock: config::Lock,
+}
+
+/// The capacities of a lock in both specs, all cells of the lock are summed.
+#[derive(Debug, Serialize)]
+pub struct CellChange {
+    lock: config::Lock,
+    before: u64,
+    after: u64,
+}
+
+/// A parameter of a spec, by its dotted path, such as `genesis.timestamp`.
+#[derive(Debug, Serialize)]
+pub struct ParamChange {
+    path: String,
+    before: Option<String>,
+    after: Option<String>,
+}
+
+/// The semantic difference of two specs, all capacities are in shannons.
+#[derive(Debug, Serialize)]
+pub struct Diff {
+    params: Vec<ParamChange>,
+    added: Vec<CellChange>,
+    removed: Vec<CellChange>,
+    changed: Vec<CellChange>,
+    supply_before: u64,
+    supply_after: u64,
+    net_supply: i64,
+}
+
+fn load(path: &str) -> Result<ChainSpec> {
+    let content = fs::read_to_string(path)?;
+    toml::from_str(&content).map_err(|err| {
+        let kind = DataErrorKind::Unexpected("not a valid spec".to_owned());
+        DataError::new(path, kind).caused_by(err).into()
+    })
+}
+
+/// Sums the capacities of the issued cells by their locks.
+fn issued_capacities(spec: &ChainSpec, file: &str) -> Result<BTreeMap<config::Lock, u64>> {
+    let cells: Vec<SpecCell> = serde_json::to_value(&spec.genesis.issued_cells)
+        .and_then(serde_json::from_value)
+        .map_err(|err| {
+            let kind = DataErrorKind::Unexpected("unexpected issued cells".to_owned());
+            DataError::new(file, kind).caused_by(err)
+        })?;
+    let mut capacities = BTreeMap::new();
+    for cell in cells {
+        let capacity = capacities.entry(cell.lock).or_insert(0u64);
+        let sum = capacity.checked_add(cell.capacity).ok_or_else(|| {
+            Error::from(DataErrorKind::TokenOverflow(format!(
+                "{} + {} shannons",
+                capacity, cell.capacity
+            )))
+        })?;
+        *capacity = sum;
+    }
+    Ok(capacities)
+}
+
+/// Flattens all parameters except the issued cells, by their dotted paths.
+fn params(spec: &ChainSpec, file: &str) -> Result<BTreeMap<String, String>> {
+    let mut value = toml::Value::try_from(spec).map_err(|err| {
+        let kind = DataErrorKind::Unexpected("failed to serialize".to_owned());
+        DataError::new(file, kind).caused_by(err)
+    })?;
+    if let Some(genesis) = value.get_mut("genesis").and_then(toml::Value::as_table_mut) {
+        genesis.remove("issued_cells");
+    }
+    let mut params = BTreeMap::new();
+    flatten(&mut params, String::new(), &value);
+    Ok(params)
+}
+
+fn flatten(params: &mut BTreeMap<String, String>, path: String, value: &toml::Value) {
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table {
+                flatten(params, join(key), value);
+            }
+        }
+        toml::Value::Array(array) => {
+            for (index, value) in array.iter().enumerate() {
+                flatten(params, join(&index.to_string()), value);
+            }
+        }
+        _ => {
+            params.insert(path, value.to_string());
+        }
+    }
+}
+
+fn signed(before: u64, after: u64) -> String {
+    if after >= before {
+        format!("+{}", Token::from_shannons(after - before))
+    } else {
+        format!("-{}", Token::from_shannons(before - after))
+    }
+}
+
+impl Diff {
+    pub fn new(before: &ChainSpec, after: &ChainSpec, files: (&str, &str)) -> Result<Self> {
+        let params = {
+            let before = params(before, files.0)?;
+            let after = params(after, files.1)?;
+            before
+                .keys()
+                .chain(after.keys())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .filter(|path| before.get(*path) != after.get(*path))
+                .map(|path| ParamChange {
+                    path: path.to_owned(),
+                    before: before.get(path).cloned(),
+                    after: after.get(path).cloned(),
+                })
+                .collect()
+        };
+        let before = issued_capacities(before, files.0)?;
+        let after = issued_capacities(after, files.1)?;
+        let mut added = Vec::new();
+        let mut removed = Vec::new();
+        let mut changed = Vec::new();
+        for lock in before.keys().chain(after.keys()).collect::<BTreeSet<_>>() {
+            let change = CellChange {
+                lock: lock.to_owned(),
+                before: before.get(lock).cloned().unwrap_or(0),
+                after: after.get(lock).cloned().unwrap_or(0),
+            };
+            if !before.contains_key(lock) {
+                added.push(change);
+            } else if !after.contains_key(lock) {
+                removed.push(change);
+            } else if change.before != change.after {
+                changed.push(change);
+            }
+        }
+        let supply_before = before
+            .values()
+            .map(|x| Token::from_shannons(*x))
+            .sum::<Result<Token>>()?;
+        let supply_after = after
+            .values()
+            .map(|x| Token::from_shannons(*x))
+            .sum::<Result<Token>>()?;
+        let net_supply = i64::try_from(
+            i128::from(supply_after.shannons()) - i128::from(supply_before.shannons()),
+        )
+        .map_err(|_| {
+            Error::from(DataErrorKind::TokenOverflow(format!(
+                "{} - {}",
+                supply_after, supply_before
+            )))
+        })?;
+        Ok(Self {
+            params,
+            added,
+            removed,
+            changed,
+            supply_before: supply_before.shannons(),
+            supply_after: supply_after.shannons(),
+            net_supply,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+    }
+
+    /// Writes the difference as a readable report.
+    pub fn write_report<W: io::Write>(&self, mut writer: W) -> Result<()> {
+        if self.is_empty() {
+            writeln!(writer, "no difference")?;
+            return Ok(());
+        }
+        writeln!(writer, "parameters: {} changed", self.params.len())?;
+        for param in &self.params {
+            writeln!(
+                writer,
+                "    {}: {} -> {}",
+                param.path,
+                param
+                    .before
+                    .as_ref()
+                    .map(String::as_str)
+                    .unwrap_or("(none)"),
+                param.after.as_ref().map(String::as_str).unwrap_or("(none)")
+            )?;
+        }
+        writeln!(
+            writer,
+            "issued cells: {} added, {} removed, {} changed",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len()
+        )?;
+        for change in &self.added {
+            writeln!(
+                writer,
+                "    + {}: {}",
+                change.lock,
+                Token::from_shannons(change.after)
+            )?;
+        }
+        for change in &self.removed {
+            writeln!(
+                writer,
+                "    - {}: {}",
+                change.lock,
+                Token::from_shannons(change.before)
+            )?;
+        }
+        for change in &self.changed {
+            writeln!(
+                writer,
+                "    ~ {}: {} -> {} ({})",
+                change.lock,
+                Token::from_shannons(change.before),
+                Token::from_shannons(change.after),
+                signed(change.before, change.after)
+            )?;
+        }
+        writeln!(
+            writer,
+            "issued supply: {} -> {} ({})",
+            Token::from_shannons(self.supply_before),
+            Token::from_shannons(self.supply_after),
+            signed(self.supply_before, self.supply_after)
+        )?;
+        Ok(())
+    }
+}
+
+pub fn run(args: &arguments::DiffArguments) -> Result<()> {
+    let files = (&args.before()[..], &args.after()[..]);
+    let before = load(files.0)?;
+    let after = load(files.1)?;
+    log::info!("diff {} -> {}", files.0, files.1);
+    let diff = Diff::new(&before, &after, files)?;
+    log::info!(
+        "    {} parameters and {} issued cells are changed",
+        diff.params.len(),
+        diff.added.len() + diff.removed.len() + diff.changed.len()
+    );
+    if args.json() {
+        serde_json::to_writer_pretty(&mut *args.output().write(), &diff)?;
+    } else {
+        diff.write_report(&mut *args.output().write())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data;
+
+    /// A dev spec with the timestamp and the issued cells, the cells are `(args, capacity)`.
+    fn spec(timestamp: i64, cells: &[(&str, u64)]) -> ChainSpec {
+        let mut value: toml::Value = toml::from_str(data::SPECS_DEV_BASE).unwrap();
+        let genesis = value["genesis"].as_table_mut().unwrap();
+        genesis.insert("timestamp".to_owned(), toml::Value::Integer(timestamp));
+        let cells = cells
+            .iter()
+            .map(|(args, capacity)| {
+                let cell = format!(
+                    "capacity = {}\n[lock]\ncode_hash = \"0x{}\"\nargs = \"{}\"\nhash_type = \"type\"",
+                    capacity,
+                    "ab".repeat(32),
+                    args
+                );
+                toml::from_str(&cell).unwrap()
+            })
+            .collect();
+        genesis.insert("issued_cells".to_owned(), toml::Value::Array(cells));
+        value.try_into().unwrap()
+    }
+
+    fn args(changes: &[CellChange]) -> Vec<(&str, u64, u64)> {
+        changes
+            .iter()
+            .map(|change| (&change.lock.args[..], change.before, change.after))
+            .collect()
+    }
+
+    #[test]
+    fn same_specs() {
+        let spec = spec(1, &[("0x01", 100), ("0x02", 200)]);
+        let diff = Diff::new(&spec, &spec, ("a", "b")).unwrap();
+        assert!(diff.is_empty());
+        assert_eq!(diff.supply_before, 300);
+        assert_eq!(diff.supply_after, 300);
+        assert_eq!(diff.net_supply, 0);
+    }
+
+    #[test]
+    fn changed_cells_and_params() {
+        let before = spec(1, &[("0x01", 100), ("0x02", 200), ("0x03", 300)]);
+        // The cells of the same lock are summed.
+        let after = spec(
+            2,
+            &[("0x01", 100), ("0x02", 150), ("0x04", 10), ("0x04", 20)],
+        );
+        let diff = Diff::new(&before, &after, ("a", "b")).unwrap();
+        assert!(!diff.is_empty());
+        assert_eq!(args(&diff.added), vec![("0x04", 0, 30)]);
+        assert_eq!(args(&diff.removed), vec![("0x03", 300, 0)]);
+        assert_eq!(args(&diff.changed), vec![("0x02", 200, 150)]);
+        assert_eq!(diff.params.len(), 1);
+        assert_eq!(diff.params[0].path, "genesis.timestamp");
+        assert_eq!(diff.params[0].before.as_ref().unwrap(), "1");
+        assert_eq!(diff.params[0].after.as_ref().unwrap(), "2");
+        assert_eq!(diff.net_supply, 280 - 600);
+    }
+
+    #[test]
+    fn readable_report() {
+        let before = spec(1, &[("0x01", 100_000_000), ("0x02", 200_000_000)]);
+        let after = spec(1, &[("0x02", 100_000_000), ("0x03", 300_000_000)]);
+        let diff = Diff::new(&before, &after, ("a", "b")).unwrap();
+        let mut report = Vec::new();
+        diff.write_report(&mut report).unwrap();
+        let report = String::from_utf8(report).unwrap();
+        let lines = report.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "parameters: 0 changed");
+        assert_eq!(lines[1], "issued cells: 1 added, 1 removed, 1 changed");
+        assert!(lines[2].starts_with("    + ") && lines[2].ends_with(": 3 CKB"));
+        assert!(lines[3].starts_with("    - ") && lines[3].ends_with(": 1 CKB"));
+        assert!(lines[4].starts_with("    ~ ") && lines[4].ends_with(": 2 CKB -> 1 CKB (-1 CKB)"));
+        assert_eq!(lines[5], "issued supply: 3 CKB -> 4 CKB (+1 CKB)");
+
+        let mut report = Vec::new();
+        let same = Diff::new(&before, &before, ("a", "b")).unwrap();
+        same.write_report(&mut report).unwrap();
+        assert_eq!(String::from_utf8(report).unwrap(), "no difference\n");
+    }
+
+    #[test]
+    fn signed_amounts() {
+        assert_eq!(signed(100_000_000, 300_000_000), "+2 CKB");
+        assert_eq!(signed(300_000_000, 100_000_000), "-2 CKB");
+        assert_eq!(signed(1, 1), "+0 CKB");
+    }
+}
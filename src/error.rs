@@ -6,12 +6,20 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::{io, num};
+use std::{error::Error as StdError, fmt, io, iter, num};
 
 use failure::Fail;
 
 use uckb_jsonrpc_client::url;
 
+pub const EXIT_INTERNAL: i32 = 1;
+pub const EXIT_ARGUMENT: i32 = 2;
+pub const EXIT_DATA: i32 = 3;
+pub const EXIT_RPC: i32 = 4;
+pub const EXIT_INVARIANT: i32 = 5;
+pub const EXIT_IO: i32 = 6;
+pub const EXIT_INTERRUPTED: i32 = 130;
+
 #[derive(Debug, Fail)]
 pub enum Error {
     #[fail(display = "internal error: should be unreachable, {}", _0)]
@@ -19,12 +27,17 @@ pub enum Error {
     #[fail(display = "internal error: {} should be implemented", _0)]
     Unimplemented(String),
 
-    #[fail(display = "data error: invalid multi signature")]
-    InvalidMultiSignature,
+    #[fail(display = "{}", _0)]
+    Data(DataError),
+    #[fail(display = "{}", _0)]
+    Rpc(RpcError),
+    #[fail(display = "{}", _0)]
+    Invariant(InvariantError),
 
     #[fail(display = "io error: {}", _0)]
     IO(io::Error),
-    #[fail(display = "number error: {}", _0)]
+    /// Only for the arguments, numbers in the input data are reported as `Data`.
+    #[fail(display = "argument error: invalid number, {}", _0)]
     Num(num::ParseIntError),
     #[fail(display = "url error: {}", _0)]
     Url(url::ParseError),
@@ -32,6 +45,8 @@ pub enum Error {
     CSV(csv::Error),
     #[fail(display = "toml error: {}", _0)]
     Toml(toml::de::Error),
+    #[fail(display = "json error: {}", _0)]
+    Json(serde_json::Error),
 
     #[fail(
         display = "argument error: the epoch is too small (expected {}, actual {})",
@@ -39,10 +54,95 @@ pub enum Error {
     )]
     EpochTooSmall(u64, u64),
     #[fail(
-        display = "argument error: the path of output file ({}) is existed",
+        display = "argument error: the path of output file ({}) is existed, use --force to replace it",
         _0
     )]
     OutputExisted(String),
+    #[fail(
+        display = "argument error: the side output ({}) of {} is existed, use --force to replace it",
+        _0, _1
+    )]
+    SideOutputExisted(String, String),
+    #[fail(
+        display = "argument error: the quorum should be in [1, {}], actual {}",
+        _1, _0
+    )]
+    InvalidQuorum(usize, usize),
+    #[fail(
+        display = "argument error: the block range [{}, {}] is invalid",
+        _0, _1
+    )]
+    InvalidRange(u64, u64),
+    #[fail(display = "argument error: invalid address '{}'", _0)]
+    InvalidAddress(String),
+    #[fail(display = "argument error: invalid network '{}'", _0)]
+    InvalidNetwork(String),
+    #[fail(display = "argument error: invalid chain config, {}", _0)]
+    InvalidChain(String),
+    #[fail(display = "argument error: invalid template variable {}", _0)]
+    InvalidVariable(String),
+    #[fail(display = "argument error: invalid averaging '{}'", _0)]
+    InvalidAveraging(String),
+    #[fail(display = "argument error: invalid compact target '{}'", _0)]
+    InvalidCompactTarget(String),
+    #[fail(display = "argument error: only one output could be the standard output")]
+    StdoutConflict,
+
+    #[fail(display = "interrupted by the user")]
+    Interrupted,
+}
+
+#[derive(Debug)]
+pub enum DataErrorKind {
+    FieldCount(usize, usize),
+    MissingField,
+    InvalidValue(String),
+    InvalidAddress(String),
+    InvalidField(String, String),
+    BelowLimit(String, String),
+    Unexpected(String),
+    InvalidMultiSignature,
+    Skipped(String),
+    InsufficientCapacity(String, u64),
+    UnspendableCells(usize, String),
+    TokenOverflow(String),
+    InvalidToken(String),
+    InvalidDate(String),
+    InvalidHex(String),
+    UnexpectedPrefix(String, String),
+}
+
+/// An error in the input data, located by the file, the line and the column.
+///
+/// The file is unknown for an error from a value alone, the caller which knows where the
+/// value comes from wraps it as the source of a located error.
+#[derive(Debug)]
+pub struct DataError {
+    file: Option<String>,
+    line: Option<u64>,
+    column: Option<usize>,
+    kind: DataErrorKind,
+    source: Option<Box<dyn Fail>>,
+}
+
+/// An error when calling a JSON-RPC method.
+#[derive(Debug)]
+pub struct RpcError {
+    method: String,
+    block: Option<u64>,
+    source: Option<Box<dyn Fail>>,
+}
+
+/// A failed supply invariant.
+#[derive(Debug, Fail)]
+#[fail(
+    display = "invariant error: {} (expected {}, actual {})",
+    name, expected, actual
+)]
+pub struct InvariantError {
+    name: String,
+    expected: String,
+    actual: String,
 }
 
 pub type Result<T> = ::std::result::Result<T, Error>;
@@ -57,8 +157,307 @@ macro_rules! convert_error {
     };
 }
 
+convert_error!(Data, DataError);
+convert_error!(Rpc, RpcError);
+convert_error!(Invariant, InvariantError);
 convert_error!(IO, io::Error);
 convert_error!(Num, num::ParseIntError);
 convert_error!(Url, url::ParseError);
 convert_error!(CSV, csv::Error);
 convert_error!(Toml, toml::de::Error);
+convert_error!(Json, serde_json::Error);
+
+impl ::std::convert::From<DataErrorKind> for Error {
+    fn from(kind: DataErrorKind) -> Self {
+        Self::Data(kind.into())
+    }
+}
+
+impl Error {
+    /// The exit code for the process, scripts could react on it.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Unreachable(_) | Self::Unimplemented(_) => EXIT_INTERNAL,
+            Self::Data(_) | Self::CSV(_) | Self::Toml(_) | Self::Json(_) => EXIT_DATA,
+            Self::Rpc(_) => EXIT_RPC,
+            Self::Invariant(_) => EXIT_INVARIANT,
+            Self::IO(_) => EXIT_IO,
+            Self::Interrupted => EXIT_INTERRUPTED,
+            Self::Num(_)
+            | Self::Url(_)
+            | Self::EpochTooSmall(_, _)
+            | Self::OutputExisted(_)
+            | Self::SideOutputExisted(_, _)
+            | Self::InvalidQuorum(_, _)
+            | Self::InvalidRange(_, _)
+            | Self::InvalidAddress(_)
+            | Self::InvalidNetwork(_)
+            | Self::InvalidChain(_)
+            | Self::InvalidVariable(_)
+            | Self::InvalidAveraging(_)
+            | Self::InvalidCompactTarget(_)
+            | Self::StdoutConflict => EXIT_ARGUMENT,
+        }
+    }
+
+    /// The errors which caused this error, from the nearest one to the root one.
+    pub fn causes(&self) -> Vec<String> {
+        let first = match self {
+            Self::Data(ref inner) => inner.cause(),
+            Self::Rpc(ref inner) => inner.cause(),
+            Self::IO(ref inner) => return sources(inner),
+            Self::Num(ref inner) => return sources(inner),
+            Self::Url(ref inner) => return sources(inner),
+            Self::CSV(ref inner) => return sources(inner),
+            Self::Toml(ref inner) => return sources(inner),
+            Self::Json(ref inner) => return sources(inner),
+            Self::Unreachable(_)
+            | Self::Unimplemented(_)
+            | Self::Invariant(_)
+            | Self::EpochTooSmall(_, _)
+            | Self::OutputExisted(_)
+            | Self::SideOutputExisted(_, _)
+            | Self::InvalidQuorum(_, _)
+            | Self::InvalidRange(_, _)
+            | Self::InvalidAddress(_)
+            | Self::InvalidNetwork(_)
+            | Self::InvalidChain(_)
+            | Self::InvalidVariable(_)
+            | Self::InvalidAveraging(_)
+            | Self::InvalidCompactTarget(_)
+            | Self::StdoutConflict
+            | Self::Interrupted => None,
+        };
+        iter::successors(first, |cause| cause.cause())
+            .map(ToString::to_string)
+            .collect()
+    }
+}
+
+/// The sources of an error from the standard library or the dependencies.
+fn sources(error: &dyn StdError) -> Vec<String> {
+    iter::successors(error.source(), |source| (*source).source())
+        .map(ToString::to_string)
+        .collect()
+}
+
+impl fmt::Display for DataErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::FieldCount(expected, actual) => {
+                write!(f, "expect {} fields, but got {}", expected, actual)
+            }
+            Self::MissingField => write!(f, "missing field"),
+            Self::InvalidValue(ref value) => write!(f, "invalid value '{}'", value),
+            Self::InvalidAddress(ref address) => write!(f, "invalid address '{}'", address),
+            Self::InvalidField(ref field, ref reason) => {
+                write!(f, "invalid field '{}', {}", field, reason)
+            }
+            Self::BelowLimit(ref limit, ref actual) => {
+                write!(f, "value {} is less than the limit {}", actual, limit)
+            }
+            Self::Unexpected(ref reason) => write!(f, "{}", reason),
+            Self::InvalidMultiSignature => write!(f, "invalid multi signature"),
+            Self::Skipped(ref entry) => write!(f, "skipped in strict mode, {}", entry),
+            Self::InsufficientCapacity(ref cell, occupied) => write!(
+                f,
+                "capacity is less than occupied {} shannons, {}",
+                occupied, cell
+            ),
+            Self::UnspendableCells(count, ref cells) => {
+                write!(f, "{} cells could never be spent, [{}]", count, cells)
+            }
+            Self::TokenOverflow(ref expr) => write!(f, "token overflow when computing {}", expr),
+            Self::InvalidToken(ref token) => write!(f, "invalid token amount '{}'", token),
+            Self::InvalidDate(ref date) => write!(f, "invalid date '{}', expect YYYY-MM-DD", date),
+            Self::InvalidHex(ref hex) => write!(f, "invalid hex string '{}'", hex),
+            Self::UnexpectedPrefix(ref actual, ref expected) => write!(
+                f,
+                "the address prefix is '{}', expect '{}'",
+                actual, expected
+            ),
+        }
+    }
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "data error: ")?;
+        if let Some(ref file) = self.file {
+            write!(f, "{}", file)?;
+            if let Some(line) = self.line {
+                write!(f, ":{}", line)?;
+                if let Some(column) = self.column {
+                    write!(f, ":{}", column + 1)?;
+                }
+            }
+            write!(f, ": ")?;
+        }
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl Fail for DataError {
+    fn cause(&self) -> Option<&dyn Fail> {
+        self.source.as_ref().map(|source| source.as_ref())
+    }
+}
+
+impl From<DataErrorKind> for DataError {
+    fn from(kind: DataErrorKind) -> Self {
+        Self {
+            file: None,
+            line: None,
+            column: None,
+            kind,
+            source: None,
+        }
+    }
+}
+
+impl DataError {
+    pub fn new(file: &str, kind: DataErrorKind) -> Self {
+        Self {
+            file: Some(file.to_owned()),
+            ..kind.into()
+        }
+    }
+
+    /// Locates the error by the position of a CSV record.
+    pub fn from_record(file: &str, record: &csv::StringRecord, kind: DataErrorKind) -> Self {
+        let mut error = Self::new(file, kind);
+        error.line = record.position().map(csv::Position::line);
+        error
+    }
+
+    pub fn at_line(mut self, line: u64) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    pub fn at_column(mut self, column: usize) -> Self {
+        self.column = Some(column);
+        self
+    }
+
+    pub fn caused_by<E: Fail>(mut self, source: E) -> Self {
+        self.source = Some(Box::new(source));
+        self
+    }
+
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    pub fn kind(&self) -> &DataErrorKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "rpc error: failed to call '{}'", self.method)?;
+        if let Some(block) = self.block {
+            write!(f, " for block {}", block)?;
+        }
+        Ok(())
+    }
+}
+
+impl Fail for RpcError {
+    fn cause(&self) -> Option<&dyn Fail> {
+        self.source.as_ref().map(|source| source.as_ref())
+    }
+}
+
+impl RpcError {
+    pub fn new<E: Fail>(method: &str, block: Option<u64>, source: E) -> Self {
+        Self {
+            method: method.to_owned(),
+            block,
+            source: Some(Box::new(source)),
+        }
+    }
+
+    pub fn without_source(method: &str, block: Option<u64>) -> Self {
+        Self {
+            method: method.to_owned(),
+            block,
+            source: None,
+        }
+    }
+
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    pub fn block(&self) -> Option<u64> {
+        self.block
+    }
+}
+
+impl InvariantError {
+    pub fn new(name: &str, expected: &str, actual: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            expected: expected.to_owned(),
+            actual: actual.to_owned(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes() {
+        let overflow: Error = DataErrorKind::TokenOverflow("1 + 2".to_owned()).into();
+        assert_eq!(overflow.exit_code(), EXIT_DATA);
+        let num = "x".parse::<u64>().unwrap_err();
+        assert_eq!(Error::from(num).exit_code(), EXIT_ARGUMENT);
+        let data = DataError::new("a.csv", DataErrorKind::MissingField);
+        assert_eq!(Error::from(data).exit_code(), EXIT_DATA);
+        let invariant = InvariantError::new("total", "1", "2");
+        assert_eq!(Error::from(invariant).exit_code(), EXIT_INVARIANT);
+        assert_eq!(Error::Unreachable(String::new()).exit_code(), EXIT_INTERNAL);
+    }
+
+    #[test]
+    fn located_and_unlocated_data_errors() {
+        let kind = DataErrorKind::InvalidToken("1 CKB".to_owned());
+        let inner = DataError::from(kind);
+        assert_eq!(inner.file(), None);
+        assert_eq!(
+            inner.to_string(),
+            "data error: invalid token amount '1 CKB'"
+        );
+        let kind = DataErrorKind::InvalidValue("1 CKB".to_owned());
+        let outer = DataError::new("a.csv", kind)
+            .at_line(3)
+            .at_column(1)
+            .caused_by(inner);
+        assert_eq!(
+            outer.to_string(),
+            "data error: a.csv:3:2: invalid value '1 CKB'"
+        );
+        assert_eq!(
+            Error::from(outer).causes(),
+            vec!["data error: invalid token amount '1 CKB'".to_owned()]
+        );
+    }
+
+    #[test]
+    fn causes_of_wrapped_errors() {
+        let inner = io::Error::new(io::ErrorKind::Other, "disk is full");
+        let csv_error = csv::Error::from(inner);
+        assert_eq!(Error::from(csv_error).causes(), vec!["disk is full"]);
+        let rpc = RpcError::new("get_tip_header", None, InvariantError::new("a", "1", "2"));
+        assert_eq!(Error::from(rpc).causes().len(), 1);
+        assert!(Error::Interrupted.causes().is_empty());
+    }
+}
@@ -0,0 +1,218 @@
+// Copyright (C) 2019 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{collections::HashMap, fs, io};
+
+use serde_derive::Serialize;
+
+use uckb_jsonrpc_client::interfaces::types::{packed, prelude::*};
+
+use crate::{
+    arguments, client,
+    error::Result,
+    module::{chain::ChainConfig, config, hash, reward::BlockReward, token::Token},
+    output::{self, Output},
+    preprocess,
+};
+
+/// A mined block, the rewards are in shannons.
+#[derive(Serialize)]
+struct BlockRow {
+    block_number: u64,
+    address_hash: String,
+    lock_hash: String,
+    code_hash: String,
+    hash_type: String,
+    lock_args: String,
+    primary_shannon: u64,
+    secondary_shannon: u64,
+    proposal_shannon: u64,
+    tx_fee_shannon: u64,
+    total_shannon: u64,
+}
+
+/// The rewards of a miner, in the same shape as `miner_reward.csv` of other rounds.
+///
+/// The block reward only counts the components of round 5.3, and the token reward is the
+/// share of the round 5.3 reward pool in CKBytes, as if the exported blocks were the round.
+#[derive(Serialize)]
+struct MinerRow {
+    address_hash: String,
+    lock_hash: String,
+    total_block_reward_shannon: u64,
+    mainnet_token_reward: u64,
+}
+
+struct LockColumns {
+    address_hash: String,
+    lock_hash: String,
+    code_hash: String,
+    hash_type: String,
+    lock_args: String,
+}
+
+impl LockColumns {
+    fn new(lock: &packed::Script, chain: &ChainConfig) -> Result<Self> {
+        let address_hash = hash::extract_from_sighash_lock(lock, &chain.scripts.sighash_code_hash)
+            .map(|hash| hash::encode_address(&hash, &chain.source.address_prefix))
+            .transpose()?
+            .unwrap_or_default();
+        let config::Lock {
+            code_hash,
+            args,
+            hash_type,
+        } = config::Lock::from_script(lock);
+        Ok(Self {
+            address_hash,
+            lock_hash: format!("{:#x}", lock.calc_script_hash()),
+            code_hash,
+            hash_type,
+            lock_args: args,
+        })
+    }
+}
+
+pub fn export_blocks(args: &arguments::ExportArguments) -> Result<()> {
+    fs::create_dir_all(args.output_dir())?;
+    let create = |name: &str| {
+        let path = args.output_dir().join(name);
+        Output::create(&path.to_string_lossy(), args.force())
+    };
+    let blocks_csv = create("blocks.csv")?;
+    let blocks_json = create("blocks.json")?;
+    let miners_csv = create("miners.csv")?;
+
+    log::info!("export blocks [{}, {}] ...", args.from(), args.to());
+    let blocks = client::fetch_blocks(args.rpc(), args.chain(), args.from(), args.to())?;
+
+    let mut block_rows = Vec::with_capacity(blocks.len());
+    let mut miners: HashMap<String, (LockColumns, BlockReward)> = HashMap::new();
+    for (number, block) in blocks.iter() {
+        let columns = LockColumns::new(block.lock(), args.chain())?;
+        let reward = block.reward();
+        block_rows.push(BlockRow {
+            block_number: *number,
+            address_hash: columns.address_hash.clone(),
+            lock_hash: columns.lock_hash.clone(),
+            code_hash: columns.code_hash.clone(),
+            hash_type: columns.hash_type.clone(),
+            lock_args: columns.lock_args.clone(),
+            primary_shannon: reward.primary().shannons(),
+            secondary_shannon: reward.secondary().shannons(),
+            proposal_shannon: reward.proposal().shannons(),
+            tx_fee_shannon: reward.tx_fee().shannons(),
+            total_shannon: reward.total()?.shannons(),
+        });
+        let miner = miners
+            .entry(columns.lock_hash.clone())
+            .or_insert_with(|| (columns, BlockReward::default()));
+        miner.1 = miner.1.checked_add(reward)?;
+    }
+
+    let block_rewards = miners
+        .into_iter()
+        .map(|(_, (columns, reward))| {
+            reward
+                .sum_of(preprocess::ROUND_5_S3_REWARD_COMPONENTS)
+                .map(|block_reward| (columns, block_reward))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let miner_rows = miner_rows(block_rewards)?;
+    log::info!(
+        "    {} blocks are mined by {} miners",
+        block_rows.len(),
+        miner_rows.len()
+    );
+
+    write_csv(&mut *blocks_csv.write(), &block_rows)?;
+    serde_json::to_writer_pretty(&mut *blocks_json.write(), &block_rows)?;
+    write_csv(&mut *miners_csv.write(), &miner_rows)?;
+    output::commit_all(&[&blocks_csv, &blocks_json, &miners_csv])
+}
+
+/// The miners with their token rewards, sorted by their block rewards in descending order.
+fn miner_rows(block_rewards: Vec<(LockColumns, Token)>) -> Result<Vec<MinerRow>> {
+    // The miners which are not counted have no token rewards.
+    let token_rewards = preprocess::split_round_5_s3_pool(
+        block_rewards
+            .iter()
+            .map(|(columns, block_reward)| (columns.lock_hash.clone(), *block_reward))
+            .collect(),
+    )?
+    .into_iter()
+    .map(|(lock_hash, _, token_reward)| (lock_hash, token_reward))
+    .collect::<HashMap<_, _>>();
+    let mut miner_rows = block_rewards
+        .into_iter()
+        .map(|(columns, block_reward)| MinerRow {
+            mainnet_token_reward: token_rewards.get(&columns.lock_hash).cloned().unwrap_or(0),
+            address_hash: columns.address_hash,
+            lock_hash: columns.lock_hash,
+            total_block_reward_shannon: block_reward.shannons(),
+        })
+        .collect::<Vec<_>>();
+    miner_rows.sort_by(|a, b| {
+        b.total_block_reward_shannon
+            .cmp(&a.total_block_reward_shannon)
+            .then_with(|| a.lock_hash.cmp(&b.lock_hash))
+    });
+    Ok(miner_rows)
+}
+
+fn write_csv<W, T>(writer: W, rows: &[T]) -> Result<()>
+where
+    W: io::Write,
+    T: serde::Serialize,
+{
+    let mut writer = csv::Writer::from_writer(writer);
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data;
+
+    fn columns(address_hash: &str, lock_hash: &str) -> LockColumns {
+        LockColumns {
+            address_hash: address_hash.to_owned(),
+            lock_hash: lock_hash.to_owned(),
+            code_hash: String::new(),
+            hash_type: "type".to_owned(),
+            lock_args: String::new(),
+        }
+    }
+
+    #[test]
+    fn miners_csv_shape() {
+        let rows = miner_rows(vec![
+            (columns("ckt1a", "0x01"), Token::from_shannons(1_000)),
+            (columns("", "0x02"), Token::from_shannons(3_000)),
+            (columns("ckt1c", "0x03"), Token::from_shannons(999)),
+        ])
+        .unwrap();
+        let mut content = Vec::new();
+        write_csv(&mut content, &rows).unwrap();
+        let content = String::from_utf8(content).unwrap();
+        let lines = content.lines().collect::<Vec<_>>();
+        // The same header as the mined rewards of round 5.1.
+        assert_eq!(lines[0], data::ROUND_5_S1_MINED.lines().next().unwrap());
+        assert_eq!(
+            &lines[1..],
+            &[
+                ",0x02,3000,13500000",
+                "ckt1a,0x01,1000,4500000",
+                "ckt1c,0x03,999,0",
+            ]
+        );
+    }
+}
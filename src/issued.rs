@@ -0,0 +1,260 @@
+// Copyright (C) 2019 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{convert::TryInto, io};
+
+use serde_derive::Serialize;
+
+use uckb_jsonrpc_client::interfaces::types::{core, prelude::*};
+
+use crate::{
+    error::{DataError, DataErrorKind, Error, Result},
+    module::{chain::Scripts, config},
+};
+
+/// An issued cell of the genesis, with its out-point and the rows which it comes from.
+#[derive(Debug, Serialize)]
+pub struct IssuedCell {
+    tx_hash: String,
+    index: u32,
+    capacity: u64,
+    lock_code_hash: String,
+    lock_args: String,
+    lock_hash_type: String,
+    lock_hash: String,
+    since: Option<u64>,
+    since_decoded: Option<String>,
+    sources: Vec<config::Source>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Issued {
+    cells: Vec<IssuedCell>,
+}
+
+fn same_lock(lhs: &config::Lock, rhs: &config::Lock) -> bool {
+    lhs.code_hash.eq_ignore_ascii_case(&rhs.code_hash)
+        && lhs.args.eq_ignore_ascii_case(&rhs.args)
+        && lhs.hash_type == rhs.hash_type
+}
+
+/// Decodes a since value, as `[relative ]block N`, `[relative ]epoch N (I/L)` or
+/// `[relative ]timestamp N`.
+fn decode_since(since: u64) -> String {
+    let value = since & 0x00ff_ffff_ffff_ffff;
+    let metric = match (since >> 61) & 0b11 {
+        0b00 => format!("block {}", value),
+        0b01 => {
+            let epoch = core::EpochNumberWithFraction::from_full_value(value);
+            format!(
+                "epoch {} ({}/{})",
+                epoch.number(),
+                epoch.index(),
+                epoch.length()
+            )
+        }
+        0b10 => format!("timestamp {}", value),
+        _ => format!("invalid {:#x}", since),
+    };
+    if since & 0x8000_0000_0000_0000 == 0 {
+        metric
+    } else {
+        format!("relative {}", metric)
+    }
+}
+
+/// The since which is in the args of a multisig lock, a 28 bytes args is the blake160 of the
+/// multisig script and the since in little endian.
+fn since_of(lock: &config::Lock, scripts: &Scripts) -> Result<Option<u64>> {
+    if !lock
+        .code_hash
+        .eq_ignore_ascii_case(&scripts.multisig_code_hash)
+    {
+        return Ok(None);
+    }
+    let args = lock.args_bytes()?;
+    let since = args
+        .get(20..28)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u64::from_le_bytes);
+    Ok(since)
+}
+
+impl Issued {
+    /// Locates the issued cells of the configuration in the cellbase of the genesis block.
+    ///
+    /// The issued cells should be consecutive outputs with the same order as the configuration,
+    /// they are searched from the end since they are the last outputs of a built spec.
+    pub fn new(
+        block: &core::BlockView,
+        cfg: &config::Configuration,
+        scripts: &Scripts,
+    ) -> Result<Self> {
+        let file = "genesis block";
+        let tx = block.transaction(0).ok_or_else(|| {
+            DataError::new(file, DataErrorKind::Unexpected("no cellbase".to_owned()))
+        })?;
+        let outputs = tx.outputs().into_iter().collect::<Vec<_>>();
+        let expected = &cfg.cells[..];
+        let offset = (0..=outputs.len().saturating_sub(expected.len()))
+            .rev()
+            .find(|offset| {
+                outputs.len() - offset >= expected.len()
+                    && outputs[*offset..]
+                        .iter()
+                        .zip(expected)
+                        .all(|(output, cell)| {
+                            let capacity: u64 = output.capacity().unpack();
+                            capacity == cell.capacity
+                                && same_lock(&config::Lock::from_script(&output.lock()), &cell.lock)
+                        })
+            })
+            .ok_or_else(|| {
+                let reason = format!(
+                    "the {} issued cells are not found in the outputs of the cellbase",
+                    expected.len()
+                );
+                DataError::new(file, DataErrorKind::Unexpected(reason))
+            })?;
+        let tx_hash = format!("{:#x}", tx.hash());
+        let cells = outputs[offset..]
+            .iter()
+            .zip(expected)
+            .enumerate()
+            .map(|(i, (output, cell))| {
+                let index = (offset + i).try_into().map_err(|_| {
+                    Error::Unreachable(format!("the output index {} overflows", offset + i))
+                })?;
+                let since = since_of(&cell.lock, scripts)?;
+                Ok(IssuedCell {
+                    tx_hash: tx_hash.clone(),
+                    index,
+                    capacity: cell.capacity,
+                    lock_code_hash: cell.lock.code_hash.clone(),
+                    lock_args: cell.lock.args.clone(),
+                    lock_hash_type: cell.lock.hash_type.clone(),
+                    lock_hash: format!("{:#x}", output.lock().calc_script_hash()),
+                    since,
+                    since_decoded: since.map(decode_since),
+                    sources: cell.sources.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        log::info!(
+            "issued cells: {}, out-points {}:[{}, {})",
+            cells.len(),
+            tx_hash,
+            offset,
+            offset + cells.len()
+        );
+        Ok(Self { cells })
+    }
+
+    /// Writes a row for each issued cell, the sources are joined by `;`.
+    pub fn write_csv<W: io::Write>(&self, writer: W) -> Result<()> {
+        let mut writer = csv::Writer::from_writer(writer);
+        writer.write_record(&[
+            "tx_hash",
+            "index",
+            "capacity",
+            "lock_code_hash",
+            "lock_args",
+            "lock_hash_type",
+            "lock_hash",
+            "since",
+            "since_decoded",
+            "sources",
+        ])?;
+        for cell in &self.cells[..] {
+            writer.write_record(&[
+                cell.tx_hash.clone(),
+                cell.index.to_string(),
+                cell.capacity.to_string(),
+                cell.lock_code_hash.clone(),
+                cell.lock_args.clone(),
+                cell.lock_hash_type.clone(),
+                cell.lock_hash.clone(),
+                cell.since
+                    .map(|since| format!("{:#x}", since))
+                    .unwrap_or_default(),
+                cell.since_decoded.clone().unwrap_or_default(),
+                cell.sources
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(";"),
+            ])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn write_json<W: io::Write>(&self, writer: W) -> Result<()> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scripts() -> Scripts {
+        Scripts {
+            sighash_code_hash: format!("0x{}", "aa".repeat(32)),
+            multisig_code_hash: format!("0x{}", "bb".repeat(32)),
+        }
+    }
+
+    fn lock(code_hash: &str, args: &str) -> config::Lock {
+        config::Lock {
+            code_hash: code_hash.to_owned(),
+            args: args.to_owned(),
+            hash_type: "type".to_owned(),
+        }
+    }
+
+    #[test]
+    fn decode_since_metrics() {
+        for (since, decoded) in &[
+            (100, "block 100"),
+            (0x2000_0100_0000_005a, "epoch 90 (0/1)"),
+            (0x2000_0300_0200_005a, "epoch 90 (2/3)"),
+            (0x4000_0000_0000_03e8, "timestamp 1000"),
+            (0x8000_0000_0000_000a, "relative block 10"),
+            (0xc000_0000_0000_03e8, "relative timestamp 1000"),
+            (0x6000_0000_0000_0000, "invalid 0x6000000000000000"),
+        ] {
+            assert_eq!(decode_since(*since), *decoded);
+        }
+    }
+
+    #[test]
+    fn since_of_multisig_locks() {
+        let scripts = scripts();
+        let since: u64 = 0x2000_0100_0000_005a;
+        let args = format!("0x{}{:016x}", "ab".repeat(20), since.swap_bytes());
+        let multisig = scripts.multisig_code_hash.clone();
+        assert_eq!(
+            since_of(&lock(&multisig, &args), &scripts).unwrap(),
+            Some(since)
+        );
+        let upper = multisig.to_uppercase().replacen("0X", "0x", 1);
+        assert_eq!(
+            since_of(&lock(&upper, &args), &scripts).unwrap(),
+            Some(since)
+        );
+        // No since in a 20 bytes args.
+        let args = format!("0x{}", "ab".repeat(20));
+        assert_eq!(since_of(&lock(&multisig, &args), &scripts).unwrap(), None);
+        // Only multisig locks have since.
+        let sighash = scripts.sighash_code_hash.clone();
+        assert_eq!(since_of(&lock(&sighash, &args), &scripts).unwrap(), None);
+        assert!(since_of(&lock(&multisig, "0xabc"), &scripts).is_err());
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright (C) 2019 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use failure::Fail;
+use hyper::{body, client::HttpConnector, header, Body, Request, StatusCode};
+use hyper_tls::HttpsConnector;
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+
+use uckb_jsonrpc_client::url;
+
+use crate::retry::Retryable;
+
+#[derive(Debug, Fail)]
+pub enum JsonRpcError {
+    #[fail(display = "invalid request: {}", _0)]
+    Request(hyper::http::Error),
+    #[fail(display = "http error: {}", _0)]
+    Http(hyper::Error),
+    #[fail(display = "http status {}", _0)]
+    Status(StatusCode),
+    #[fail(display = "invalid response: {}", _0)]
+    Json(serde_json::Error),
+    #[fail(display = "error {}: {}", _0, _1)]
+    Response(i64, String),
+    #[fail(display = "not found")]
+    NotFound,
+}
+
+impl Retryable for JsonRpcError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            Self::Http(_) => true,
+            Self::Status(status) => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            Self::Request(_) | Self::Json(_) | Self::Response(..) | Self::NotFound => false,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct RequestBody<'a> {
+    jsonrpc: &'static str,
+    id: u64,
+    method: &'a str,
+    params: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct ResponseError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct ResponseBody<T> {
+    result: Option<T>,
+    error: Option<ResponseError>,
+}
+
+/// An asynchronous JSON-RPC client over HTTP.
+///
+/// A request is cancelled when its future is dropped, so a timed out request holds nothing.
+pub struct HttpClient {
+    url: url::Url,
+    inner: hyper::Client<HttpsConnector<HttpConnector>>,
+    id: AtomicU64,
+}
+
+impl HttpClient {
+    pub fn new(url: url::Url) -> Self {
+        Self {
+            url,
+            inner: hyper::Client::builder().build(HttpsConnector::new()),
+            id: AtomicU64::new(0),
+        }
+    }
+
+    /// Calls a method, a `null` result is an error since all methods which are used return
+    /// `null` only when the data is not found.
+    pub async fn request<T>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<T, JsonRpcError>
+    where
+        T: DeserializeOwned,
+    {
+        let request = RequestBody {
+            jsonrpc: "2.0",
+            id: self.id.fetch_add(1, Ordering::SeqCst),
+            method,
+            params,
+        };
+        let content = serde_json::to_vec(&request).map_err(JsonRpcError::Json)?;
+        let request = Request::post(self.url.as_str())
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(content))
+            .map_err(JsonRpcError::Request)?;
+        let response = self
+            .inner
+            .request(request)
+            .await
+            .map_err(JsonRpcError::Http)?;
+        if !response.status().is_success() {
+            return Err(JsonRpcError::Status(response.status()));
+        }
+        let content = body::to_bytes(response.into_body())
+            .await
+            .map_err(JsonRpcError::Http)?;
+        let response: ResponseBody<T> =
+            serde_json::from_slice(&content).map_err(JsonRpcError::Json)?;
+        if let Some(error) = response.error {
+            return Err(JsonRpcError::Response(error.code, error.message));
+        }
+        response.result.ok_or(JsonRpcError::NotFound)
+    }
+}
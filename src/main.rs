@@ -7,24 +7,69 @@
 // except according to those terms.
 
 pub mod arguments;
+pub mod audit;
 pub mod client;
 pub mod constants;
 pub mod data;
+pub mod diff;
 pub mod error;
+pub mod export;
+pub mod issued;
+pub mod jsonrpc;
+pub mod manifest;
 pub mod module;
+pub mod output;
 pub mod preprocess;
+pub mod retry;
+pub mod simulate;
+pub mod spec;
+pub mod target;
 pub mod template;
 
 use std::{env, str};
 
 fn execute() -> error::Result<()> {
-    let args = arguments::build_commandline()?;
-    let chain_data = client::fetch(&args)?;
-    let mut cfg = module::config::Configuration::default();
-    cfg.update_by_last_header(chain_data.header());
-    let (cells, target) = preprocess::process(&args, &chain_data, &cfg)?;
-    cfg.append_cells(cells).update_target(target);
-    template::fill(&args, &cfg)
+    match arguments::build_commandline()? {
+        arguments::Command::Generate(args) => {
+            generate(&args)?;
+            output::commit_all(&args.outputs())
+        }
+        arguments::Command::ExportBlocks(args) => export::export_blocks(&args),
+        arguments::Command::Simulate(args) => {
+            simulate::run(&args)?;
+            output::commit_all(&args.outputs())
+        }
+        arguments::Command::Diff(args) => {
+            diff::run(&args)?;
+            output::commit_all(&args.outputs())
+        }
+    }
+}
+
+fn generate(args: &arguments::Arguments) -> error::Result<()> {
+    let chain_data = client::fetch(args)?;
+    let mut cfg = module::config::Configuration::new(args.chain());
+    cfg.update_by_last_header(chain_data.header(), &args.chain().genesis.message_prefix);
+    let mut ledger = module::ledger::Ledger::new(args.strict());
+    let mut audit = audit::Audit::default();
+    let (cells, report) = preprocess::process(args, &chain_data, &cfg, &mut ledger, &mut audit)?;
+    report.log();
+    if let Some(file) = args.target_report() {
+        report.write_json(&mut *file.write())?;
+    }
+    // The skipped entries and the audit are saved even if the audit fails.
+    if let Some(file) = args.skipped() {
+        ledger.write_csv(&mut *file.write())?;
+        file.commit()?;
+    }
+    if let Some(file) = args.audit() {
+        audit.write_json(&mut *file.write())?;
+        file.commit()?;
+    }
+    audit.finish()?;
+    cfg.append_cells(cells).update_target(report.target());
+    cfg.check_occupied_capacity()?;
+    spec::generate(args, &chain_data, &cfg)
 }
 
 fn main() {
@@ -40,6 +85,9 @@ fn main() {
 
     if let Err(error) = execute() {
         eprintln!("Fatal: {}", error);
-        ::std::process::exit(1);
+        for cause in error.causes() {
+            eprintln!("    caused by: {}", cause);
+        }
+        ::std::process::exit(error.exit_code());
     }
 }
@@ -0,0 +1,233 @@
+// Copyright (C) 2019 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{collections::BTreeMap, env, fs, io};
+
+use serde_derive::Serialize;
+use sha2::{Digest, Sha256};
+
+use uckb_jsonrpc_client::interfaces::types::core;
+
+use crate::{arguments, client, data, error::Result};
+
+/// The build of the tool.
+#[derive(Debug, Serialize)]
+pub struct Tool {
+    name: String,
+    version: String,
+    git_commit: String,
+}
+
+/// The chain snapshot which the genesis is generated from.
+#[derive(Debug, Serialize)]
+pub struct Chain {
+    urls: Vec<String>,
+    epoch: u64,
+    last_block_number: u64,
+    last_block_hash: String,
+    data_sha256: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Outputs {
+    spec_sha256: String,
+    genesis_hash: String,
+}
+
+/// Records the inputs and the outputs of a generation, so the spec could be reproduced.
+///
+/// Nothing depends on the time, the environment or the output paths, the same inputs produce
+/// the same manifest.
+#[derive(Debug, Serialize)]
+pub struct Manifest {
+    tool: Tool,
+    arguments: Vec<String>,
+    /// The SHA-256 of all inputs, by the names of the inputs.
+    inputs: BTreeMap<String, String>,
+    chain: Chain,
+    outputs: Outputs,
+}
+
+/// The options which only choose where the outputs are saved, they don't change the outputs.
+const OUTPUT_OPTIONS: &[&str] = &["--output", "--skipped", "--emit-block"];
+
+/// The command line arguments without the output options, so the manifest does not depend
+/// on where the outputs are saved.
+fn reproducible_arguments<I>(args: I) -> Vec<String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter();
+    let mut kept = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg == "--force" {
+            continue;
+        }
+        if OUTPUT_OPTIONS.contains(&arg.as_str()) {
+            // Skip the value too.
+            let _ = args.next();
+            continue;
+        }
+        let is_output = OUTPUT_OPTIONS
+            .iter()
+            .any(|option| arg.starts_with(&format!("{}=", option)));
+        if !is_output {
+            kept.push(arg);
+        }
+    }
+    kept
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    faster_hex::hex_string(&Sha256::digest(data)).unwrap_or_default()
+}
+
+impl Manifest {
+    pub fn new(
+        args: &arguments::Arguments,
+        chain_data: &client::ChainData,
+        spec: &str,
+        block: &core::BlockView,
+    ) -> Result<Self> {
+        let tool = Tool {
+            name: env!("CARGO_PKG_NAME").to_owned(),
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            git_commit: env!("GBGC_GIT_COMMIT").to_owned(),
+        };
+        let mut inputs = BTreeMap::new();
+        for (path, content) in data::EMBEDDED_FILES {
+            inputs.insert((*path).to_owned(), sha256_hex(content.as_bytes()));
+        }
+        inputs.insert(
+            format!("chain config '{}'", args.chain().genesis.name),
+            sha256_hex(&serde_json::to_vec(args.chain())?),
+        );
+        if let Some(path) = args.template() {
+            inputs.insert(format!("template '{}'", path), sha256_hex(&fs::read(path)?));
+            inputs.insert(
+                "template variables".to_owned(),
+                sha256_hex(&serde_json::to_vec(args.vars())?),
+            );
+        } else {
+            inputs.insert(
+                format!("built-in {} base spec", args.network()),
+                sha256_hex(args.network().spec_base().as_bytes()),
+            );
+        }
+        let header = chain_data.header();
+        let chain = Chain {
+            urls: args.rpc().urls().iter().map(ToString::to_string).collect(),
+            // As specified by `--epoch`, the arguments keep the next one.
+            epoch: args.epoch() - 1,
+            last_block_number: header.number(),
+            last_block_hash: format!("{:#x}", header.hash()),
+            data_sha256: chain_data.digest(),
+        };
+        let outputs = Outputs {
+            spec_sha256: sha256_hex(spec.as_bytes()),
+            genesis_hash: format!("{:#x}", block.hash()),
+        };
+        Ok(Self {
+            tool,
+            arguments: reproducible_arguments(env::args().skip(1)),
+            inputs,
+            chain,
+            outputs,
+        })
+    }
+
+    pub fn write_json<W: io::Write>(&self, writer: W) -> Result<()> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| (*arg).to_owned()).collect()
+    }
+
+    #[test]
+    fn output_options_are_not_recorded() {
+        let args = strings(&[
+            "--url",
+            "http://127.0.0.1:8114",
+            "--output",
+            "spec.toml",
+            "--epoch",
+            "89",
+            "--skipped=skipped.csv",
+            "--force",
+            "--emit-block",
+            "genesis",
+            "--template",
+            "template.toml",
+        ]);
+        assert_eq!(
+            reproducible_arguments(args),
+            strings(&[
+                "--url",
+                "http://127.0.0.1:8114",
+                "--epoch",
+                "89",
+                "--template",
+                "template.toml",
+            ])
+        );
+        let args = strings(&["--output-dir", "--network", "dev"]);
+        assert_eq!(reproducible_arguments(args.clone()), args);
+    }
+
+    #[test]
+    fn sha256_of_data() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn manifest_shape() {
+        let manifest = Manifest {
+            tool: Tool {
+                name: "ckb-gbgc".to_owned(),
+                version: "0.1.0".to_owned(),
+                git_commit: "unknown".to_owned(),
+            },
+            arguments: strings(&["--epoch", "89"]),
+            inputs: vec![("spec".to_owned(), sha256_hex(b"spec"))]
+                .into_iter()
+                .collect(),
+            chain: Chain {
+                urls: strings(&["http://127.0.0.1:8114/"]),
+                epoch: 89,
+                last_block_number: 1000,
+                last_block_hash: format!("0x{}", "00".repeat(32)),
+                data_sha256: sha256_hex(b"data"),
+            },
+            outputs: Outputs {
+                spec_sha256: sha256_hex(b"spec"),
+                genesis_hash: format!("0x{}", "11".repeat(32)),
+            },
+        };
+        let mut content = Vec::new();
+        manifest.write_json(&mut content).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&content).unwrap();
+        let mut keys = value.as_object().unwrap().keys().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec!["arguments", "chain", "inputs", "outputs", "tool"]
+        );
+        assert_eq!(value["chain"]["epoch"], 89);
+        assert_eq!(value["outputs"]["spec_sha256"], sha256_hex(b"spec"));
+    }
+}
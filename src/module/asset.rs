@@ -13,20 +13,19 @@ use property::Property;
 use uckb_jsonrpc_client::interfaces::{blake2b, types::core};
 
 use super::{
-    config::{Cell, Lock},
-    hash::H160,
+    chain::{ChainConfig, Scripts},
+    config::{Cell, Lock, Source},
+    hash::{self, H160},
     timestamp,
     token::Token,
 };
-use crate::{
-    constants,
-    error::{Error, Result},
-};
+use crate::error::{DataErrorKind, Error, Result};
 
 #[derive(Debug, Property)]
 pub struct Asset {
     owner: Owner,
     token: Token,
+    sources: Vec<Source>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -38,6 +37,9 @@ pub enum Owner {
         threshold: u8,
         since: u64,
     },
+    /// A lock which is kept as it is, such as a multisig lock from the chain, since the hashes
+    /// of its keys are unknown.
+    Script(Lock),
 }
 
 impl fmt::Display for Asset {
@@ -80,6 +82,7 @@ impl fmt::Display for Owner {
                 write!(f, "] ")?;
                 write!(f, "}}")
             }
+            Self::Script(ref lock) => write!(f, "Script {}", lock),
         }
     }
 }
@@ -107,12 +110,21 @@ impl fmt::LowerHex for Owner {
                 args.extend_from_slice(&since.to_le_bytes()[..]);
                 write!(f, "{}", faster_hex::hex_string(&args[..]).unwrap())
             }
+            Self::Script(ref lock) => write!(f, "{}", lock.args.trim_start_matches("0x")),
         }
     }
 }
 
 impl cmp::Ord for Owner {
     fn cmp(&self, other: &Self) -> cmp::Ordering {
+        match (self, other) {
+            (Self::Script(ref self_lock), Self::Script(ref other_lock)) => {
+                return self_lock.cmp(other_lock)
+            }
+            (Self::Script(_), _) => return cmp::Ordering::Greater,
+            (_, Self::Script(_)) => return cmp::Ordering::Less,
+            _ => {}
+        }
         match (self.is_single(), other.is_single()) {
             (true, false) => cmp::Ordering::Less,
             (false, true) => cmp::Ordering::Greater,
@@ -173,19 +185,36 @@ impl cmp::PartialOrd for Owner {
 }
 
 impl Asset {
-    pub fn into_cell(self) -> Cell {
-        Cell {
-            capacity: self.token().shannons(),
-            lock: Lock {
+    /// Records a row which the asset comes from.
+    pub fn with_source(mut self, file: &str, row: Option<u64>) -> Self {
+        self.sources.push(Source::new(file, row));
+        self
+    }
+
+    pub fn with_sources(mut self, mut sources: Vec<Source>) -> Self {
+        self.sources.append(&mut sources);
+        self
+    }
+
+    pub fn into_cell(self, scripts: &Scripts) -> Cell {
+        let lock = if let Owner::Script(ref lock) = self.owner {
+            lock.clone()
+        } else {
+            Lock {
                 code_hash: if self.owner().is_single() {
-                    constants::SECP256K1_BLAKE160_SIGHASH_CODE_HASH
+                    &scripts.sighash_code_hash
                 } else {
-                    constants::SECP256K1_BLAKE160_MULTISIG_CODE_HASH
+                    &scripts.multisig_code_hash
                 }
                 .to_owned(),
                 args: format!("{:#x}", self.owner()),
                 hash_type: "type".to_owned(),
-            },
+            }
+        };
+        Cell {
+            capacity: self.token().shannons(),
+            lock,
+            sources: self.sources,
         }
     }
 }
@@ -201,17 +230,38 @@ impl Owner {
         threshold: u8,
         since_str: &str,
         epoch: u64,
+        chain: &ChainConfig,
     ) -> Result<Self> {
         if hashes.len() >= usize::from(threshold) && threshold >= require_first_n {
             Ok(Self::Multi {
                 hashes,
                 require_first_n,
                 threshold,
-                since: parse_since_from_str(since_str, epoch)?,
+                since: parse_since_from_str(since_str, epoch, chain)?,
             })
         } else {
-            Err(Error::InvalidMultiSignature)
+            Err(DataErrorKind::InvalidMultiSignature.into())
+        }
+    }
+
+    /// Maps a lock from the chain to an owner, only the SECP256K1 + blake160 sighash and
+    /// multisig locks are supported.
+    ///
+    /// A multisig lock with 28 bytes args carries a since of the source chain, which means
+    /// nothing on the new chain, so it is not supported either.
+    pub fn from_lock(lock: &Lock, scripts: &Scripts) -> Result<Option<Self>> {
+        if lock.hash_type != "type" {
+            return Ok(None);
         }
+        let args = lock.args_bytes()?;
+        let owner = if lock.code_hash == scripts.sighash_code_hash {
+            hash::extract_from_slice(&args).map(Self::Single)
+        } else if lock.code_hash == scripts.multisig_code_hash && args.len() == 20 {
+            Some(Self::Script(lock.clone()))
+        } else {
+            None
+        };
+        Ok(owner)
     }
 
     pub fn is_single(&self) -> bool {
@@ -226,58 +276,55 @@ impl Owner {
         !self.is_single()
     }
 
-    pub fn with_bytes(self, bytes: u64) -> Asset {
-        Asset {
-            owner: self,
-            token: Token::from_bytes(bytes),
-        }
+    pub fn with_bytes(self, bytes: u64) -> Result<Asset> {
+        Token::from_bytes(bytes).map(|token| self.with_token(token))
     }
 
     pub fn with_shannons(self, shannons: u64) -> Asset {
-        Asset {
-            owner: self,
-            token: Token::from_shannons(shannons),
-        }
+        self.with_token(Token::from_shannons(shannons))
     }
 
     pub fn with_token(self, token: Token) -> Asset {
-        Asset { owner: self, token }
+        Asset {
+            owner: self,
+            token,
+            sources: Vec::new(),
+        }
     }
 }
 
-fn parse_since_from_str(date: &str, epoch: u64) -> Result<u64> {
+/// Converts a date to an absolute epoch since.
+///
+/// The planned epoch of the chain config is at the origin of the since, and each epoch lasts
+/// for the epoch duration target.
+fn parse_since_from_str(date: &str, epoch: u64, chain: &ChainConfig) -> Result<u64> {
+    let invalid = || Error::from(DataErrorKind::InvalidDate(date.to_owned()));
     let mut date_split = date.split('-');
     let year = date_split
         .next()
-        .ok_or_else(|| Error::Unreachable(format!("split year from '{}'", date)))?
-        .parse::<u64>()?;
+        .and_then(|year| year.parse::<u64>().ok())
+        .ok_or_else(invalid)?;
     let month = date_split
         .next()
-        .ok_or_else(|| Error::Unreachable(format!("split month from '{}'", date)))?
-        .parse::<u8>()?;
+        .and_then(|month| month.parse::<u8>().ok())
+        .ok_or_else(invalid)?;
     let day = date_split
         .next()
-        .ok_or_else(|| Error::Unreachable(format!("split day from '{}'", date)))?
-        .parse::<u8>()?;
+        .and_then(|day| day.parse::<u8>().ok())
+        .ok_or_else(invalid)?;
     if date_split.next().is_some() {
-        return Err(Error::Unreachable(format!(
-            "'{}' has redundant fields",
-            date
-        )));
+        return Err(invalid());
     }
-    let start = timestamp::timestamp(2019, 11, 16, 6, 0, 0).ok_or_else(|| {
-        Error::Unreachable("failed to compute timestamp for 2019-11-16 06-00-00".to_owned())
-    })?;
-    let end = timestamp::timestamp(year, month, day, 0, 0, 0).ok_or_else(|| {
-        Error::Unreachable(format!(
-            "failed to compute timestamp for {}-{}-{} 06-00-00",
-            year, month, day
-        ))
-    })?;
-    let epoches = (end - start) / (60 * 60 * 4);
-    let remainder = (end - start) % (60 * 60 * 4);
+    let start = chain.since.origin;
+    let duration = chain.consensus.epoch_duration_target;
+    let planned_epoch = chain.consensus.planned_epoch;
+    let length = chain.since.epoch_length;
+    let end = timestamp::timestamp(year, month, day, 0, 0, 0).ok_or_else(invalid)?;
+    let elapsed = end.saturating_sub(start);
+    let epoches = elapsed / duration;
+    let remainder = elapsed % duration;
     log::trace!(
-        "2019-11-16 06:00:00 ({}) ~ {:04}-{:02}-{:02} 00:00:00 ({}), {}, {}",
+        "origin ({}) ~ {:04}-{:02}-{:02} 00:00:00 ({}), {}, {}",
         start,
         year,
         month,
@@ -287,18 +334,100 @@ fn parse_since_from_str(date: &str, epoch: u64) -> Result<u64> {
         remainder
     );
     let target_epoch = {
-        let (number, index) = if epoches + constants::PLANNED_EPOCH > epoch {
+        let (number, index) = if epoches + planned_epoch > epoch {
             (
-                epoches + constants::PLANNED_EPOCH - epoch,
-                remainder * 1800 / (60 * 60 * 4),
+                epoches + planned_epoch - epoch,
+                remainder * length / duration,
             )
         } else {
             (0, 0)
         };
-        let length = 1800;
         core::EpochNumberWithFraction::new(number, index, length)
     };
     log::trace!("            target_epoch = {:#}", target_epoch);
     let since = target_epoch.full_value() | 0x2000_0000_0000_0000;
     Ok(since)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module::network::Network;
+
+    fn scripts() -> Scripts {
+        Scripts {
+            sighash_code_hash: format!("0x{}", "11".repeat(32)),
+            multisig_code_hash: format!("0x{}", "22".repeat(32)),
+        }
+    }
+
+    fn lock(code_hash: &str, args_len: usize, hash_type: &str) -> Lock {
+        Lock {
+            code_hash: code_hash.to_owned(),
+            args: format!("0x{}", "ab".repeat(args_len)),
+            hash_type: hash_type.to_owned(),
+        }
+    }
+
+    fn since(number: u64, index: u64, length: u64) -> u64 {
+        0x2000_0000_0000_0000 | number | (index << 24) | (length << 40)
+    }
+
+    #[test]
+    fn since_from_dates() {
+        let mut chain = ChainConfig::load(None, Network::Mainnet).unwrap();
+        // 18 hours after the origin, 4 epochs and a half.
+        assert_eq!(
+            parse_since_from_str("2019-11-17", 1, &chain).unwrap(),
+            since(93, 900, 1800)
+        );
+        // The epoch after the planned epoch is already passed.
+        assert_eq!(
+            parse_since_from_str("2019-11-17", 100, &chain).unwrap(),
+            since(0, 0, 1800)
+        );
+        assert!(parse_since_from_str("2019-11", 1, &chain).is_err());
+        assert!(parse_since_from_str("2019-11-31", 1, &chain).is_err());
+
+        chain.since.origin -= 6 * 60 * 60;
+        chain.since.epoch_length = 1000;
+        chain.consensus.planned_epoch = 10;
+        assert_eq!(
+            parse_since_from_str("2019-11-17", 1, &chain).unwrap(),
+            since(15, 0, 1000)
+        );
+    }
+
+    #[test]
+    fn sighash_locks() {
+        let scripts = scripts();
+        let single = lock(&scripts.sighash_code_hash, 20, "type");
+        assert_eq!(
+            Owner::from_lock(&single, &scripts).unwrap(),
+            Some(Owner::Single([0xab; 20]))
+        );
+        let wrong_args = lock(&scripts.sighash_code_hash, 28, "type");
+        assert_eq!(Owner::from_lock(&wrong_args, &scripts).unwrap(), None);
+    }
+
+    #[test]
+    fn multisig_locks() {
+        let scripts = scripts();
+        let multi = lock(&scripts.multisig_code_hash, 20, "type");
+        assert_eq!(
+            Owner::from_lock(&multi, &scripts).unwrap(),
+            Some(Owner::Script(multi.clone()))
+        );
+        let with_since = lock(&scripts.multisig_code_hash, 28, "type");
+        assert_eq!(Owner::from_lock(&with_since, &scripts).unwrap(), None);
+    }
+
+    #[test]
+    fn unsupported_locks() {
+        let scripts = scripts();
+        let data_hash_type = lock(&scripts.sighash_code_hash, 20, "data");
+        assert_eq!(Owner::from_lock(&data_hash_type, &scripts).unwrap(), None);
+        let unknown = lock(&format!("0x{}", "33".repeat(32)), 20, "type");
+        assert_eq!(Owner::from_lock(&unknown, &scripts).unwrap(), None);
+    }
+}
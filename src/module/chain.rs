@@ -0,0 +1,363 @@
+// Copyright (C) 2019 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fs;
+
+use serde_derive::{Deserialize, Serialize};
+
+use super::{hash, network::Network};
+use crate::{
+    data,
+    error::{DataError, DataErrorKind, Error, Result},
+};
+
+/// The version of the chain config schema which is supported.
+pub const CHAIN_CONFIG_VERSION: u32 = 1;
+
+/// The constants and the network parameters of a chain.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainConfig {
+    pub version: u32,
+    pub genesis: Genesis,
+    pub source: Source,
+    pub scripts: Scripts,
+    pub foundation: Foundation,
+    pub since: Since,
+    pub satoshi_gift: SatoshiGift,
+    pub consensus: Consensus,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Genesis {
+    pub name: String,
+    pub message_prefix: String,
+    pub timestamp: u64,
+    pub epoch_length: u64,
+    pub initial_total_supply: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Source {
+    pub address_prefix: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scripts {
+    pub sighash_code_hash: String,
+    pub multisig_code_hash: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Foundation {
+    pub address: String,
+    pub since: String,
+    pub testnet_address: String,
+}
+
+/// How a date is converted to an epoch since.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Since {
+    // In seconds.
+    pub origin: u64,
+    pub epoch_length: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SatoshiGift {
+    pub code_hash: String,
+    pub args: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Consensus {
+    pub confirmations: u64,
+    pub epoch_avg_count: u64,
+    pub planned_epoch: u64,
+    pub epoch_duration_target: u64,
+    pub min_block_interval: u64,
+    pub max_block_interval: u64,
+}
+
+fn is_hex(hex: &str, bytes_len: Option<usize>) -> bool {
+    hex.starts_with("0x")
+        && hex.len() % 2 == 0
+        && hex[2..].chars().all(|c| c.is_ascii_hexdigit())
+        && bytes_len
+            .map(|len| hex.len() == len * 2 + 2)
+            .unwrap_or(true)
+}
+
+fn is_date(date: &str) -> bool {
+    let fields = date.split('-').collect::<Vec<_>>();
+    fields.len() == 3
+        && fields[0].parse::<u64>().is_ok()
+        && fields[1]
+            .parse::<u8>()
+            .map(|m| m >= 1 && m <= 12)
+            .unwrap_or(false)
+        && fields[2]
+            .parse::<u8>()
+            .map(|d| d >= 1 && d <= 31)
+            .unwrap_or(false)
+}
+
+impl ChainConfig {
+    /// Loads a built-in preset by its name, or a chain config file by its path.
+    ///
+    /// The preset of the network is loaded if no chain is specified, and a preset of another
+    /// network is rejected.
+    pub fn load(name_or_path: Option<&str>, network: Network) -> Result<Self> {
+        let name_or_path = name_or_path
+            .or_else(|| network.chain_preset())
+            .ok_or_else(|| {
+                Error::InvalidChain(format!(
+                    "no built-in preset for {}, specify a chain config file by --chain",
+                    network
+                ))
+            })?;
+        let preset = data::CHAIN_PRESETS
+            .iter()
+            .find(|(name, _)| *name == name_or_path);
+        let (file, content) = if let Some((name, content)) = preset {
+            if network.chain_preset() != Some(*name) {
+                return Err(Error::InvalidChain(format!(
+                    "the preset '{}' is not for {}",
+                    name, network
+                )));
+            }
+            (format!("preset '{}'", name), (*content).to_owned())
+        } else {
+            (name_or_path.to_owned(), fs::read_to_string(name_or_path)?)
+        };
+        let config: Self = toml::from_str(&content).map_err(|err| {
+            let kind = DataErrorKind::Unexpected("not a valid chain config".to_owned());
+            DataError::new(&file, kind).caused_by(err)
+        })?;
+        config.validate(&file, network)?;
+        log::info!("chain config: {}", file);
+        Ok(config)
+    }
+
+    /// Checks all values, the first invalid field is returned as an error.
+    pub fn validate(&self, file: &str, network: Network) -> Result<()> {
+        let invalid = |field: &str, reason: String| -> Result<()> {
+            let kind = DataErrorKind::InvalidField(field.to_owned(), reason);
+            Err(DataError::new(file, kind).into())
+        };
+        if self.version != CHAIN_CONFIG_VERSION {
+            return invalid(
+                "version",
+                format!("expect {}, actual {}", CHAIN_CONFIG_VERSION, self.version),
+            );
+        }
+        if self.genesis.name.is_empty() {
+            return invalid("genesis.name", "should not be empty".to_owned());
+        }
+        if self.genesis.epoch_length == 0 {
+            return invalid("genesis.epoch_length", "should not be zero".to_owned());
+        }
+        if self.genesis.initial_total_supply == 0 {
+            return invalid(
+                "genesis.initial_total_supply",
+                "should not be zero".to_owned(),
+            );
+        }
+        let prefix = &self.source.address_prefix;
+        if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_lowercase()) {
+            return invalid(
+                "source.address_prefix",
+                format!("'{}' is not an address prefix", prefix),
+            );
+        }
+        for (field, code_hash) in &[
+            ("scripts.sighash_code_hash", &self.scripts.sighash_code_hash),
+            (
+                "scripts.multisig_code_hash",
+                &self.scripts.multisig_code_hash,
+            ),
+            ("satoshi_gift.code_hash", &self.satoshi_gift.code_hash),
+        ] {
+            if !is_hex(code_hash, Some(32)) {
+                return invalid(field, format!("'{}' is not a 32 bytes hash", code_hash));
+            }
+        }
+        if !is_hex(&self.satoshi_gift.args, None) {
+            return invalid(
+                "satoshi_gift.args",
+                format!("'{}' is not a hex string", self.satoshi_gift.args),
+            );
+        }
+        for (field, address) in &[
+            ("foundation.address", &self.foundation.address),
+            (
+                "foundation.testnet_address",
+                &self.foundation.testnet_address,
+            ),
+        ] {
+            if hash::extract_from_address_with_prefixes(address, network.address_prefixes())
+                .and_then(|result| result.ok())
+                .is_none()
+            {
+                return invalid(field, format!("'{}' is not a {} address", address, network));
+            }
+        }
+        if !is_date(&self.foundation.since) {
+            return invalid(
+                "foundation.since",
+                format!("'{}' is not a date as YYYY-MM-DD", self.foundation.since),
+            );
+        }
+        if self.since.origin == 0 {
+            return invalid("since.origin", "should not be zero".to_owned());
+        }
+        if self.since.epoch_length == 0 || self.since.epoch_length >= 1 << 16 {
+            return invalid(
+                "since.epoch_length",
+                format!("{} is not in [1, 65535]", self.since.epoch_length),
+            );
+        }
+        let consensus = &self.consensus;
+        for (field, value) in &[
+            ("consensus.confirmations", consensus.confirmations),
+            ("consensus.epoch_avg_count", consensus.epoch_avg_count),
+            (
+                "consensus.epoch_duration_target",
+                consensus.epoch_duration_target,
+            ),
+            ("consensus.min_block_interval", consensus.min_block_interval),
+        ] {
+            if *value == 0 {
+                return invalid(field, "should not be zero".to_owned());
+            }
+        }
+        if consensus.max_block_interval < consensus.min_block_interval {
+            return invalid(
+                "consensus.max_block_interval",
+                format!(
+                    "{} is less than the min block interval {}",
+                    consensus.max_block_interval, consensus.min_block_interval
+                ),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    fn lina() -> ChainConfig {
+        ChainConfig::load(None, Network::Mainnet).unwrap()
+    }
+
+    /// The field which is reported by the validation.
+    fn invalid_field(config: &ChainConfig, network: Network) -> String {
+        match config.validate("chain.toml", network) {
+            Err(Error::Data(err)) => match err.kind() {
+                DataErrorKind::InvalidField(field, _) => {
+                    assert_eq!(err.file(), Some("chain.toml"));
+                    field.to_owned()
+                }
+                kind => panic!("unexpected error: {}", kind),
+            },
+            _ => panic!("the config should be invalid"),
+        }
+    }
+
+    #[test]
+    fn validate_presets() {
+        let config = lina();
+        assert!(config.validate("chain.toml", Network::Mainnet).is_ok());
+        assert_eq!(config.source.address_prefix, "ckt");
+        assert_eq!(config.since.origin, 1_573_884_000);
+        assert_eq!(config.since.epoch_length, 1800);
+    }
+
+    #[test]
+    fn validate_invalid_fields() {
+        let mut config = lina();
+        config.version = CHAIN_CONFIG_VERSION + 1;
+        assert_eq!(invalid_field(&config, Network::Mainnet), "version");
+
+        let mut config = lina();
+        config.source.address_prefix = "CKT".to_owned();
+        assert_eq!(
+            invalid_field(&config, Network::Mainnet),
+            "source.address_prefix"
+        );
+
+        let mut config = lina();
+        config.scripts.multisig_code_hash = "0x1234".to_owned();
+        assert_eq!(
+            invalid_field(&config, Network::Mainnet),
+            "scripts.multisig_code_hash"
+        );
+
+        let mut config = lina();
+        config.foundation.since = "2020-13-01".to_owned();
+        assert_eq!(invalid_field(&config, Network::Mainnet), "foundation.since");
+
+        let mut config = lina();
+        config.since.epoch_length = 0;
+        assert_eq!(
+            invalid_field(&config, Network::Mainnet),
+            "since.epoch_length"
+        );
+
+        let mut config = lina();
+        config.consensus.max_block_interval = config.consensus.min_block_interval - 1;
+        assert_eq!(
+            invalid_field(&config, Network::Mainnet),
+            "consensus.max_block_interval"
+        );
+    }
+
+    #[test]
+    fn validate_addresses_by_network() {
+        let mut config = lina();
+        config.foundation.address = hash::encode_address(&[0xab; 20], "ckt").unwrap();
+        assert_eq!(
+            invalid_field(&config, Network::Mainnet),
+            "foundation.address"
+        );
+        assert!(config.validate("chain.toml", Network::Testnet).is_ok());
+    }
+
+    #[test]
+    fn load_files() {
+        let path = env::temp_dir().join(format!("ckb-gbgc-chain-{}.toml", process::id()));
+        let path_str = path.to_string_lossy().into_owned();
+        let content = toml::to_string(&lina()).unwrap();
+        fs::write(&path, &content).unwrap();
+        let config = ChainConfig::load(Some(&path_str), Network::Mainnet).unwrap();
+        assert_eq!(config.genesis.name, "ckb");
+
+        fs::write(&path, format!("{}\nunknown = 1\n", content)).unwrap();
+        match ChainConfig::load(Some(&path_str), Network::Mainnet) {
+            Err(Error::Data(err)) => assert_eq!(err.file(), Some(&path_str[..])),
+            _ => panic!("unknown fields should be rejected"),
+        }
+        fs::remove_file(&path).unwrap();
+
+        match ChainConfig::load(Some(&path_str), Network::Mainnet) {
+            Err(Error::IO(_)) => {}
+            _ => panic!("the file is removed"),
+        }
+    }
+}
@@ -6,15 +6,24 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use serde_derive::Serialize;
+use std::fmt;
 
-use uckb_jsonrpc_client::interfaces::types::{core, prelude::*, H256};
+use serde_derive::{Deserialize, Serialize};
+
+use uckb_jsonrpc_client::interfaces::types::{core, packed, prelude::*, H256};
+
+use super::{chain::ChainConfig, token};
+use crate::error::{DataErrorKind, Error, Result};
+
+pub const CAPACITY_BYTES: u64 = 8;
+pub const CODE_HASH_BYTES: u64 = 32;
+pub const HASH_TYPE_BYTES: u64 = 1;
 
 #[derive(Serialize)]
 pub struct Configuration {
     pub name: String,
     pub timestamp: u64,
-    pub compact_target: String,
+    pub compact_target: u32,
     pub message: String,
     pub cells: Vec<Cell>,
     pub genesis_epoch_length: u64,
@@ -24,31 +33,152 @@ pub struct Configuration {
 pub struct Cell {
     pub capacity: u64,
     pub lock: Lock,
+    /// The rows which the capacity comes from, they are not a part of the spec.
+    #[serde(skip)]
+    pub sources: Vec<Source>,
 }
 
-#[derive(Serialize)]
+/// A row of a data file, or a part which is computed without any file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Source {
+    pub file: String,
+    pub row: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
 pub struct Lock {
     pub code_hash: String,
     pub args: String,
     pub hash_type: String,
 }
 
-impl Default for Configuration {
-    fn default() -> Self {
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Cell {{ capacity: {}, lock: {{ code_hash: {}, args: {}, hash_type: {} }} }}",
+            self.capacity, self.lock.code_hash, self.lock.args, self.lock.hash_type
+        )
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(row) = self.row {
+            write!(f, ":{}", row)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Lock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{{ code_hash: {}, args: {}, hash_type: {} }}",
+            self.code_hash, self.args, self.hash_type
+        )
+    }
+}
+
+/// Computes the occupied bytes of a cell which has a lock without type script.
+pub fn occupied_bytes(args_len: u64, data_len: u64) -> u64 {
+    CAPACITY_BYTES + CODE_HASH_BYTES + HASH_TYPE_BYTES + args_len + data_len
+}
+
+fn hex_bytes_len(hex: &str) -> Result<u64> {
+    if hex.starts_with("0x")
+        && hex.len() % 2 == 0
+        && hex[2..].chars().all(|c| c.is_ascii_hexdigit())
+    {
+        Ok((hex.len() as u64 - 2) / 2)
+    } else {
+        Err(DataErrorKind::InvalidHex(hex.to_owned()).into())
+    }
+}
+
+impl Lock {
+    pub fn from_script(script: &packed::Script) -> Self {
+        let hash_type = match script.hash_type().as_slice() {
+            [0] => "data",
+            [1] => "type",
+            _ => "unknown",
+        };
         Self {
-            name: "ckb".to_owned(),
-            timestamp: 1_573_833_600_000,
-            compact_target: "0x10000000".to_owned(),
-            message: format!("lina 0x{:064}", 0),
-            cells: Vec::new(),
-            genesis_epoch_length: 1000,
+            code_hash: format!("{:#x}", script.code_hash()),
+            args: format!(
+                "0x{}",
+                faster_hex::hex_string(&script.args().raw_data()).unwrap_or_default()
+            ),
+            hash_type: hash_type.to_owned(),
+        }
+    }
+
+    /// The raw bytes of the args.
+    pub fn args_bytes(&self) -> Result<Vec<u8>> {
+        let len = hex_bytes_len(&self.args)? as usize;
+        let mut bytes = vec![0; len];
+        faster_hex::hex_decode(self.args[2..].as_bytes(), &mut bytes)
+            .map_err(|_| Error::from(DataErrorKind::InvalidHex(self.args.clone())))?;
+        Ok(bytes)
+    }
+}
+
+impl Source {
+    pub fn new(file: &str, row: Option<u64>) -> Self {
+        Self {
+            file: file.to_owned(),
+            row,
+        }
+    }
+}
+
+impl Cell {
+    pub fn occupied_bytes(&self) -> Result<u64> {
+        hex_bytes_len(&self.lock.args).map(|args_len| occupied_bytes(args_len, 0))
+    }
+
+    pub fn check_occupied_capacity(&self) -> Result<()> {
+        let occupied = token::Token::from_bytes(self.occupied_bytes()?)?;
+        if token::Token::from_shannons(self.capacity) < occupied {
+            let kind = DataErrorKind::InsufficientCapacity(self.to_string(), occupied.shannons());
+            Err(kind.into())
+        } else {
+            Ok(())
         }
     }
 }
 
 impl Configuration {
+    pub fn new(chain: &ChainConfig) -> Self {
+        Self {
+            name: chain.genesis.name.clone(),
+            timestamp: chain.genesis.timestamp,
+            compact_target: 0x1000_0000,
+            message: format!("{} 0x{:064}", chain.genesis.message_prefix, 0),
+            cells: Vec::new(),
+            genesis_epoch_length: chain.genesis.epoch_length,
+        }
+    }
+
+    pub fn check_occupied_capacity(&self) -> Result<()> {
+        let mut invalid = Vec::new();
+        for cell in &self.cells[..] {
+            if let Err(err) = cell.check_occupied_capacity() {
+                log::error!("{}", err);
+                invalid.push(cell.to_string());
+            }
+        }
+        if invalid.is_empty() {
+            Ok(())
+        } else {
+            Err(DataErrorKind::UnspendableCells(invalid.len(), invalid.join("; ")).into())
+        }
+    }
+
     pub fn update_target(&mut self, target: u32) -> &mut Self {
-        self.compact_target = format!("{:#x}", target);
+        self.compact_target = target;
         self
     }
 
@@ -57,12 +187,16 @@ impl Configuration {
         self
     }
 
-    pub fn update_by_last_header(&mut self, header: &core::HeaderView) -> &mut Self {
+    pub fn update_by_last_header(
+        &mut self,
+        header: &core::HeaderView,
+        message_prefix: &str,
+    ) -> &mut Self {
         let block_hash = header.hash().unpack();
         let epoch_length = header.epoch().length();
         let timestamp: u64 = header.timestamp();
         self.update_timestamp(timestamp)
-            .update_message(&block_hash)
+            .update_message(message_prefix, &block_hash)
             .update_epoch_length(epoch_length)
     }
 
@@ -76,8 +210,60 @@ impl Configuration {
         self
     }
 
-    fn update_message(&mut self, hash: &H256) -> &mut Self {
-        self.message = format!("lina {:#x}", hash);
+    fn update_message(&mut self, prefix: &str, hash: &H256) -> &mut Self {
+        self.message = format!("{} {:#x}", prefix, hash);
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(capacity: u64, args: &str) -> Cell {
+        Cell {
+            capacity,
+            lock: Lock {
+                code_hash: format!("0x{}", "00".repeat(32)),
+                args: args.to_owned(),
+                hash_type: "type".to_owned(),
+            },
+            sources: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn occupied_bytes_of_locks() {
+        // A sighash lock has a 20 bytes args, a multisig lock with since has 28 bytes.
+        assert_eq!(occupied_bytes(20, 0), 61);
+        assert_eq!(occupied_bytes(28, 0), 69);
+        assert_eq!(occupied_bytes(0, 0), 41);
+        assert_eq!(occupied_bytes(20, 100), 161);
+    }
+
+    #[test]
+    fn occupied_bytes_of_cells() {
+        let args = format!("0x{}", "ab".repeat(20));
+        assert_eq!(cell(0, &args).occupied_bytes().unwrap(), 61);
+        assert_eq!(cell(0, "0x").occupied_bytes().unwrap(), 41);
+        assert!(cell(0, "0xabc").occupied_bytes().is_err());
+        assert!(cell(0, "abcd").occupied_bytes().is_err());
+        assert!(cell(0, "0xzz").occupied_bytes().is_err());
+    }
+
+    #[test]
+    fn check_occupied_capacity() {
+        let args = format!("0x{}", "ab".repeat(20));
+        let shannons = token::BYTE_SHANNONS;
+        assert!(cell(61 * shannons, &args).check_occupied_capacity().is_ok());
+        match cell(61 * shannons - 1, &args).check_occupied_capacity() {
+            Err(Error::Data(err)) => match err.kind() {
+                DataErrorKind::InsufficientCapacity(_, occupied) => {
+                    assert_eq!(*occupied, 61 * shannons)
+                }
+                _ => panic!("the capacity should be insufficient"),
+            },
+            _ => panic!("the capacity should be insufficient"),
+        }
+    }
+}
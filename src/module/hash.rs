@@ -6,23 +6,26 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::mem;
+use bech32::{FromBase32, ToBase32};
 
-use bech32::FromBase32;
+use uckb_jsonrpc_client::interfaces::types::{packed, prelude::*};
 
-use crate::error::{Error, Result};
+use crate::error::{DataError, DataErrorKind, Error, Result};
 
 pub type H160 = [u8; 20];
 
 pub mod deprecated {
     use super::*;
 
-    pub fn extract_from_address(address: &str) -> Option<Result<H160>> {
+    pub fn extract_from_address(address: &str, hrp_expected: &str) -> Option<Result<H160>> {
         bech32::decode(address)
             .ok()
             .and_then(|(ref hrp, ref base32)| {
-                if hrp != "ckt" {
-                    Some(Err(Error::Unreachable(format!("hrp('{}') != 'ckt'", hrp))))
+                if hrp != hrp_expected {
+                    Some(Err(Error::from(DataErrorKind::UnexpectedPrefix(
+                        hrp.to_owned(),
+                        hrp_expected.to_owned(),
+                    ))))
                 } else {
                     Vec::<u8>::from_base32(base32).ok().and_then(|bytes| {
                         if bytes.len() != 25 {
@@ -32,8 +35,7 @@ pub mod deprecated {
                         } else if &bytes[1..5] != b"P2PH" {
                             Some(Err(Error::Unimplemented("bin-idx != P2PH".to_owned())))
                         } else {
-                            let mut hash: H160 =
-                                unsafe { mem::MaybeUninit::uninit().assume_init() };
+                            let mut hash = H160::default();
                             hash.copy_from_slice(&bytes[5..]);
                             Some(Ok(hash))
                         }
@@ -43,14 +45,15 @@ pub mod deprecated {
     }
 }
 
-fn extract_from_address_inner(address: &str, hrp_expected: &str) -> Option<Result<H160>> {
+/// Extracts the hash from a short version address which has the expected prefix.
+pub fn extract_from_address(address: &str, hrp_expected: &str) -> Option<Result<H160>> {
     bech32::decode(address)
         .ok()
         .and_then(|(ref hrp, ref base32)| {
             if hrp != hrp_expected {
-                Some(Err(Error::Unreachable(format!(
-                    "hrp('{}') != '{}'",
-                    hrp, hrp_expected
+                Some(Err(Error::from(DataErrorKind::UnexpectedPrefix(
+                    hrp.to_owned(),
+                    hrp_expected.to_owned(),
                 ))))
             } else {
                 Vec::<u8>::from_base32(base32).ok().and_then(|bytes| {
@@ -60,12 +63,19 @@ fn extract_from_address_inner(address: &str, hrp_expected: &str) -> Option<Resul
                         Some(Err(Error::Unimplemented(
                             "format type != short version".to_owned(),
                         )))
+                    } else if bytes.len() != 22 {
+                        let reason = format!(
+                            "the payload of a short version address is {} bytes, expect 22",
+                            bytes.len()
+                        );
+                        let kind = DataErrorKind::Unexpected(reason);
+                        Some(Err(DataError::new(address, kind).into()))
                     } else if bytes[1] != 0x00 {
                         Some(Err(Error::Unimplemented(
                             "code_hash_index != SECP256K1 + blake160".to_owned(),
                         )))
                     } else {
-                        let mut hash: H160 = unsafe { mem::MaybeUninit::uninit().assume_init() };
+                        let mut hash = H160::default();
                         hash.copy_from_slice(&bytes[2..]);
                         Some(Ok(hash))
                     }
@@ -74,20 +84,98 @@ fn extract_from_address_inner(address: &str, hrp_expected: &str) -> Option<Resul
         })
 }
 
-pub fn extract_from_address(address: &str) -> Option<Result<H160>> {
-    extract_from_address_inner(address, "ckt")
-}
-
-pub fn extract_from_address_mainnet(address: &str) -> Option<Result<H160>> {
-    extract_from_address_inner(address, "ckb")
+/// Extracts the hash from an address which has any of the prefixes.
+pub fn extract_from_address_with_prefixes(
+    address: &str,
+    prefixes: &[&str],
+) -> Option<Result<H160>> {
+    let (hrp, _) = bech32::decode(address).ok()?;
+    let prefix = prefixes
+        .iter()
+        .find(|prefix| **prefix == hrp)
+        .or_else(|| prefixes.first())?;
+    extract_from_address(address, prefix)
 }
 
 pub fn extract_from_slice(slice: &[u8]) -> Option<H160> {
     if slice.len() == 20 {
-        let mut hash: H160 = unsafe { mem::MaybeUninit::uninit().assume_init() };
+        let mut hash = H160::default();
         hash.copy_from_slice(slice);
         Some(hash)
     } else {
         None
     }
 }
+
+/// Extracts the hash from a SECP256K1 + blake160 sighash lock, by the code hash of the chain.
+pub fn extract_from_sighash_lock(lock: &packed::Script, sighash_code_hash: &str) -> Option<H160> {
+    let code_hash = format!("{:#x}", lock.code_hash());
+    if code_hash != sighash_code_hash || lock.hash_type().as_slice() != [1] {
+        None
+    } else {
+        extract_from_slice(&lock.args().raw_data())
+    }
+}
+
+/// Encodes a hash as a short version address for SECP256K1 + blake160.
+pub fn encode_address(hash: &H160, hrp: &str) -> Result<String> {
+    let mut payload = vec![0x01, 0x00];
+    payload.extend_from_slice(&hash[..]);
+    bech32::encode(hrp, payload.to_base32())
+        .map_err(|err| Error::Unreachable(format!("failed to encode address: {}", err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(hrp: &str, payload: &[u8]) -> String {
+        bech32::encode(hrp, payload.to_base32()).unwrap()
+    }
+
+    #[test]
+    fn short_version_addresses() {
+        let hash = [0xab; 20];
+        let encoded = encode_address(&hash, "ckb").unwrap();
+        assert_eq!(
+            extract_from_address(&encoded, "ckb").unwrap().unwrap(),
+            hash
+        );
+        match extract_from_address(&encoded, "ckt") {
+            Some(Err(Error::Data(err))) => match err.kind() {
+                DataErrorKind::UnexpectedPrefix(actual, expected) => {
+                    assert_eq!((&actual[..], &expected[..]), ("ckb", "ckt"))
+                }
+                _ => panic!("the prefix should be unexpected"),
+            },
+            _ => panic!("the prefix should be unexpected"),
+        }
+        let prefixes = ["ckt", "ckb"];
+        assert_eq!(
+            extract_from_address_with_prefixes(&encoded, &prefixes)
+                .unwrap()
+                .unwrap(),
+            hash
+        );
+        assert!(extract_from_address("not an address", "ckb").is_none());
+    }
+
+    #[test]
+    fn payloads_of_invalid_lengths() {
+        for payload in &[&[0x01][..], &[0x01, 0x00, 0xab][..], &[0x01; 23][..]] {
+            let encoded = address("ckb", payload);
+            match extract_from_address(&encoded, "ckb") {
+                Some(Err(Error::Data(err))) => assert_eq!(err.file(), Some(&encoded[..])),
+                _ => panic!("the payload of '{}' should be invalid", encoded),
+            }
+        }
+        assert!(extract_from_address(&address("ckb", &[]), "ckb").is_none());
+    }
+
+    #[test]
+    fn hashes_from_slices() {
+        assert_eq!(extract_from_slice(&[0xab; 20]), Some([0xab; 20]));
+        assert_eq!(extract_from_slice(&[0xab; 21]), None);
+        assert_eq!(extract_from_slice(&[]), None);
+    }
+}
@@ -0,0 +1,271 @@
+// Copyright (C) 2019 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{fmt, io};
+
+use super::{chain::Scripts, config::Lock, token::Token};
+use crate::error::{DataErrorKind, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    UndecodableAddress,
+    UnsupportedLock(String),
+    MultisigWithSince,
+}
+
+/// An entry which can not be issued as is, its amount is forfeited, or redirected to the
+/// fallback owner if `redirected_to` is set.
+#[derive(Debug)]
+pub struct SkippedEntry {
+    file: String,
+    row: Option<u64>,
+    address: String,
+    reason: SkipReason,
+    amount: Token,
+    redirected_to: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Ledger {
+    strict: bool,
+    entries: Vec<SkippedEntry>,
+}
+
+impl SkipReason {
+    /// The reason why a lock from the chain is not mapped to an owner.
+    pub fn of_lock(lock: &Lock, scripts: &Scripts) -> Self {
+        if lock.code_hash == scripts.multisig_code_hash && lock.args.len() == 2 + 28 * 2 {
+            Self::MultisigWithSince
+        } else {
+            Self::UnsupportedLock(lock.code_hash.clone())
+        }
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UndecodableAddress => write!(f, "failed to decode address"),
+            Self::UnsupportedLock(ref code_hash) => {
+                write!(f, "unsupported lock with code hash {}", code_hash)
+            }
+            Self::MultisigWithSince => write!(f, "multisig lock with a since of the source chain"),
+        }
+    }
+}
+
+impl fmt::Display for SkippedEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(row) = self.row {
+            write!(f, ":{}", row)?;
+        }
+        write!(f, " address '{}' ({}), ", self.address, self.reason)?;
+        if let Some(ref owner) = self.redirected_to {
+            write!(f, "redirected {} to {}", self.amount, owner)
+        } else {
+            write!(f, "forfeited {}", self.amount)
+        }
+    }
+}
+
+impl Ledger {
+    pub fn new(strict: bool) -> Self {
+        Self {
+            strict,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn skip(
+        &mut self,
+        file: &str,
+        row: Option<u64>,
+        address: String,
+        reason: SkipReason,
+        amount: Token,
+    ) -> Result<()> {
+        let entry = SkippedEntry {
+            file: file.to_owned(),
+            row,
+            address,
+            reason,
+            amount,
+            redirected_to: None,
+        };
+        if self.strict {
+            Err(DataErrorKind::Skipped(entry.to_string()).into())
+        } else {
+            log::warn!("skipped: {}", entry);
+            self.entries.push(entry);
+            Ok(())
+        }
+    }
+
+    /// Records an entry which is paid to the fallback owner instead.
+    ///
+    /// It's never an error even in strict mode: nothing is lost, and the fallback is specified
+    /// explicitly by the user for these entries.
+    pub fn redirect(
+        &mut self,
+        file: &str,
+        row: Option<u64>,
+        address: String,
+        reason: SkipReason,
+        amount: Token,
+        owner: String,
+    ) {
+        let entry = SkippedEntry {
+            file: file.to_owned(),
+            row,
+            address,
+            reason,
+            amount,
+            redirected_to: Some(owner),
+        };
+        log::warn!("redirected: {}", entry);
+        self.entries.push(entry);
+    }
+
+    pub fn count_forfeited(&self) -> usize {
+        self.forfeited().count()
+    }
+
+    pub fn total_forfeited(&self) -> Result<Token> {
+        self.forfeited().map(|entry| entry.amount).sum()
+    }
+
+    pub fn count_redirected(&self) -> usize {
+        self.entries.len() - self.count_forfeited()
+    }
+
+    pub fn total_redirected(&self) -> Result<Token> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.redirected_to.is_some())
+            .map(|entry| entry.amount)
+            .sum()
+    }
+
+    fn forfeited(&self) -> impl Iterator<Item = &SkippedEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.redirected_to.is_none())
+    }
+
+    pub fn write_csv<W: io::Write>(&self, writer: W) -> Result<()> {
+        let mut writer = csv::Writer::from_writer(writer);
+        writer.write_record(&[
+            "file",
+            "row",
+            "address",
+            "reason",
+            "forfeited_shannons",
+            "redirected_shannons",
+            "redirected_to",
+        ])?;
+        for entry in &self.entries[..] {
+            let (forfeited, redirected) = if entry.redirected_to.is_some() {
+                (0, entry.amount.shannons())
+            } else {
+                (entry.amount.shannons(), 0)
+            };
+            writer.write_record(&[
+                entry.file.clone(),
+                entry.row.map(|row| row.to_string()).unwrap_or_default(),
+                entry.address.clone(),
+                entry.reason.to_string(),
+                forfeited.to_string(),
+                redirected.to_string(),
+                entry.redirected_to.clone().unwrap_or_default(),
+            ])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    fn skip(ledger: &mut Ledger, row: u64, shannons: u64) -> Result<()> {
+        ledger.skip(
+            "round.csv",
+            Some(row),
+            format!("addr{}", row),
+            SkipReason::UndecodableAddress,
+            Token::from_shannons(shannons),
+        )
+    }
+
+    fn redirect(ledger: &mut Ledger, row: u64, shannons: u64) {
+        ledger.redirect(
+            "round.csv",
+            Some(row),
+            format!("addr{}", row),
+            SkipReason::UnsupportedLock("0x01".to_owned()),
+            Token::from_shannons(shannons),
+            "fallback".to_owned(),
+        )
+    }
+
+    #[test]
+    fn skip_in_lenient_mode() {
+        let mut ledger = Ledger::new(false);
+        skip(&mut ledger, 2, 100).unwrap();
+        skip(&mut ledger, 3, 200).unwrap();
+        assert_eq!(ledger.count_forfeited(), 2);
+        assert_eq!(ledger.total_forfeited().unwrap(), Token::from_shannons(300));
+        assert_eq!(ledger.count_redirected(), 0);
+        assert_eq!(ledger.total_redirected().unwrap(), Token::zero());
+    }
+
+    #[test]
+    fn skip_in_strict_mode() {
+        let mut ledger = Ledger::new(true);
+        match skip(&mut ledger, 2, 100) {
+            Err(Error::Data(err)) => match err.kind() {
+                DataErrorKind::Skipped(entry) => assert!(entry.starts_with("round.csv:2 ")),
+                kind => panic!("unexpected error: {}", kind),
+            },
+            _ => panic!("should be an error in strict mode"),
+        }
+        assert_eq!(ledger.count_forfeited(), 0);
+    }
+
+    #[test]
+    fn redirect_in_both_modes() {
+        for strict in &[false, true] {
+            let mut ledger = Ledger::new(*strict);
+            redirect(&mut ledger, 4, 400);
+            assert_eq!(ledger.count_redirected(), 1);
+            assert_eq!(
+                ledger.total_redirected().unwrap(),
+                Token::from_shannons(400)
+            );
+            assert_eq!(ledger.count_forfeited(), 0);
+        }
+    }
+
+    #[test]
+    fn write_csv() {
+        let mut ledger = Ledger::new(false);
+        skip(&mut ledger, 2, 100).unwrap();
+        redirect(&mut ledger, 4, 400);
+        let mut content = Vec::new();
+        ledger.write_csv(&mut content).unwrap();
+        assert_eq!(
+            String::from_utf8(content).unwrap(),
+            "file,row,address,reason,forfeited_shannons,redirected_shannons,redirected_to\n\
+             round.csv,2,addr2,failed to decode address,100,0,\n\
+             round.csv,4,addr4,unsupported lock with code hash 0x01,0,400,fallback\n"
+        );
+    }
+}
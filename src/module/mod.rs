@@ -7,7 +7,11 @@
 // except according to those terms.
 
 pub mod asset;
+pub mod chain;
 pub mod config;
 pub mod hash;
+pub mod ledger;
+pub mod network;
+pub mod reward;
 pub mod timestamp;
 pub mod token;
@@ -0,0 +1,180 @@
+// Copyright (C) 2019 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{fmt, str};
+
+use crate::{
+    data,
+    error::{Error, Result},
+};
+
+/// The kind of the chain which the genesis is generated for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Dev,
+}
+
+/// The rules of the supply which differ between networks.
+#[derive(Debug, Clone, Copy)]
+pub struct SupplyRules {
+    /// The bytes which are spent by the foundation in the reference genesis, it is audited if
+    /// it is known.
+    pub foundation_spent: Option<u64>,
+    /// Lock the dated allocations and the foundation reserve by since, or issue them unlocked.
+    pub time_locks: bool,
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Mainnet => write!(f, "mainnet"),
+            Self::Testnet => write!(f, "testnet"),
+            Self::Dev => write!(f, "dev"),
+        }
+    }
+}
+
+impl str::FromStr for Network {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "mainnet" => Ok(Self::Mainnet),
+            "testnet" => Ok(Self::Testnet),
+            "dev" => Ok(Self::Dev),
+            _ => Err(Error::InvalidNetwork(s.to_owned())),
+        }
+    }
+}
+
+impl Network {
+    /// The prefixes of the addresses in the allocations and the chain config.
+    ///
+    /// Testnets and devnets also accept mainnet addresses, so the mainnet allocations could be
+    /// reused.
+    pub fn address_prefixes(self) -> &'static [&'static str] {
+        match self {
+            Self::Mainnet => &["ckb"],
+            Self::Testnet | Self::Dev => &["ckt", "ckb"],
+        }
+    }
+
+    /// The built-in chain preset of the network, it's the default of `--chain`.
+    pub fn chain_preset(self) -> Option<&'static str> {
+        match self {
+            Self::Mainnet => Some("mainnet-lina"),
+            Self::Testnet => Some("testnet-aggron"),
+            Self::Dev => Some("dev"),
+        }
+    }
+
+    /// The template of the spec.
+    ///
+    /// The spec name and the genesis message of a network come from its chain config, so the
+    /// templates only differ in the PoW function, a dev chain mines without real work.
+    pub fn spec_base(self) -> &'static str {
+        match self {
+            Self::Mainnet | Self::Testnet => data::SPECS_BASE,
+            Self::Dev => data::SPECS_DEV_BASE,
+        }
+    }
+
+    /// The bundled spec of `ckb-resource`, the foundation spent is computed from it.
+    pub fn reference_spec(self) -> &'static str {
+        match self {
+            Self::Mainnet => "specs/mainnet.toml",
+            Self::Testnet => "specs/testnet.toml",
+            Self::Dev => "specs/dev.toml",
+        }
+    }
+
+    pub fn supply_rules(self) -> SupplyRules {
+        match self {
+            Self::Mainnet => SupplyRules {
+                foundation_spent: Some(1_264_963),
+                time_locks: true,
+            },
+            Self::Testnet => SupplyRules {
+                foundation_spent: None,
+                time_locks: true,
+            },
+            Self::Dev => SupplyRules {
+                foundation_spent: None,
+                time_locks: false,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module::chain::ChainConfig;
+
+    const NETWORKS: &[Network] = &[Network::Mainnet, Network::Testnet, Network::Dev];
+
+    #[test]
+    fn parse_networks() {
+        for network in NETWORKS {
+            assert_eq!(network.to_string().parse::<Network>().unwrap(), *network);
+        }
+        assert!("lina".parse::<Network>().is_err());
+    }
+
+    #[test]
+    fn presets_of_networks() {
+        for network in NETWORKS {
+            let chain = ChainConfig::load(None, *network).unwrap();
+            let preset = network.chain_preset().unwrap();
+            assert!(ChainConfig::load(Some(preset), *network).is_ok());
+            for other in NETWORKS.iter().filter(|other| *other != network) {
+                assert!(ChainConfig::load(Some(preset), *other).is_err());
+            }
+            let names = NETWORKS
+                .iter()
+                .map(|network| ChainConfig::load(None, *network).unwrap().genesis.name)
+                .filter(|name| *name == chain.genesis.name)
+                .count();
+            assert_eq!(names, 1);
+        }
+    }
+
+    #[test]
+    fn supply_rules_of_networks() {
+        let mainnet = Network::Mainnet.supply_rules();
+        assert_eq!(mainnet.foundation_spent, Some(1_264_963));
+        assert!(mainnet.time_locks);
+        let testnet = Network::Testnet.supply_rules();
+        assert_eq!(testnet.foundation_spent, None);
+        assert!(testnet.time_locks);
+        let dev = Network::Dev.supply_rules();
+        assert_eq!(dev.foundation_spent, None);
+        assert!(!dev.time_locks);
+    }
+
+    #[test]
+    fn spec_bases_of_networks() {
+        for network in NETWORKS {
+            let base: toml::Value = toml::from_str(network.spec_base()).unwrap();
+            let func = if *network == Network::Dev {
+                "Dummy"
+            } else {
+                "Eaglesong"
+            };
+            assert_eq!(base["pow"]["func"].as_str(), Some(func));
+        }
+    }
+
+    #[test]
+    fn address_prefixes_of_networks() {
+        assert_eq!(Network::Mainnet.address_prefixes(), &["ckb"]);
+        assert_eq!(Network::Testnet.address_prefixes()[0], "ckt");
+        assert_eq!(Network::Dev.address_prefixes()[0], "ckt");
+    }
+}
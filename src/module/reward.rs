@@ -0,0 +1,100 @@
+// Copyright (C) 2019 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+
+use property::Property;
+
+use super::token::Token;
+use crate::error::Result;
+
+/// A component of the reward in a cellbase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Component {
+    Primary,
+    Secondary,
+    Proposal,
+    TxFee,
+}
+
+/// The reward of a block, it is issued in the cellbase which is `confirmations` later.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Property)]
+pub struct BlockReward {
+    primary: Token,
+    secondary: Token,
+    proposal: Token,
+    tx_fee: Token,
+}
+
+impl fmt::Display for Component {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Primary => write!(f, "primary"),
+            Self::Secondary => write!(f, "secondary"),
+            Self::Proposal => write!(f, "proposal"),
+            Self::TxFee => write!(f, "tx fee"),
+        }
+    }
+}
+
+impl fmt::Display for BlockReward {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "primary {}, secondary {}, proposal {}, tx fee {}",
+            self.primary, self.secondary, self.proposal, self.tx_fee
+        )
+    }
+}
+
+impl BlockReward {
+    pub fn new(primary: Token, secondary: Token, proposal: Token, tx_fee: Token) -> Self {
+        Self {
+            primary,
+            secondary,
+            proposal,
+            tx_fee,
+        }
+    }
+
+    pub fn component(&self, component: Component) -> Token {
+        match component {
+            Component::Primary => self.primary,
+            Component::Secondary => self.secondary,
+            Component::Proposal => self.proposal,
+            Component::TxFee => self.tx_fee,
+        }
+    }
+
+    /// The sum of the chosen components.
+    pub fn sum_of(&self, components: &[Component]) -> Result<Token> {
+        components
+            .iter()
+            .map(|component| self.component(*component))
+            .sum()
+    }
+
+    pub fn total(&self) -> Result<Token> {
+        self.sum_of(&[
+            Component::Primary,
+            Component::Secondary,
+            Component::Proposal,
+            Component::TxFee,
+        ])
+    }
+
+    /// Adds two rewards component-wise.
+    pub fn checked_add(&self, rhs: &Self) -> Result<Self> {
+        Ok(Self {
+            primary: (self.primary + rhs.primary)?,
+            secondary: (self.secondary + rhs.secondary)?,
+            proposal: (self.proposal + rhs.proposal)?,
+            tx_fee: (self.tx_fee + rhs.tx_fee)?,
+        })
+    }
+}
@@ -6,14 +6,32 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-#[derive(Debug, Clone, Copy)]
+use std::{fmt, iter, ops, str};
+
+use crate::error::{DataErrorKind, Error, Result};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Token(u64);
 
+#[derive(Debug, Clone, Copy)]
+pub struct Ratio {
+    numer: u64,
+    denom: u64,
+}
+
 pub const BYTE_SHANNONS: u64 = 100_000_000;
+const BYTE_DECIMALS: usize = 8;
 
 impl Token {
-    pub fn from_bytes(bytes: u64) -> Self {
-        Self(bytes.checked_mul(BYTE_SHANNONS).unwrap())
+    pub fn zero() -> Self {
+        Self(0)
+    }
+
+    pub fn from_bytes(bytes: u64) -> Result<Self> {
+        bytes
+            .checked_mul(BYTE_SHANNONS)
+            .map(Self)
+            .ok_or_else(|| Error::from(DataErrorKind::TokenOverflow(format!("{} bytes", bytes))))
     }
 
     pub fn from_shannons(shannons: u64) -> Self {
@@ -23,4 +41,240 @@ impl Token {
     pub fn shannons(self) -> u64 {
         self.0
     }
+
+    /// The whole bytes, the fractional part is discarded.
+    pub fn bytes(self) -> u64 {
+        self.0 / BYTE_SHANNONS
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let integer = self.bytes().to_string();
+        let fraction = self.0 % BYTE_SHANNONS;
+        let mut grouped = String::with_capacity(integer.len() + integer.len() / 3);
+        for (index, digit) in integer.chars().enumerate() {
+            if index > 0 && (integer.len() - index) % 3 == 0 {
+                grouped.push(',');
+            }
+            grouped.push(digit);
+        }
+        if fraction == 0 {
+            write!(f, "{} CKB", grouped)
+        } else {
+            let fraction = format!("{:0width$}", fraction, width = BYTE_DECIMALS);
+            write!(f, "{}.{} CKB", grouped, fraction.trim_end_matches('0'))
+        }
+    }
+}
+
+/// Parses decimal CKB amounts, such as "1,048,617.12345678 CKB" or "61", and
+/// shannon amounts, such as "100_000_000 shannons".
+///
+/// The unit is CKB if it is omitted.
+impl str::FromStr for Token {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::from(DataErrorKind::InvalidToken(s.to_owned()));
+        let input = s.trim();
+        let (number, is_shannons) = if let Some(number) = strip_suffix(input, "shannons") {
+            (number, true)
+        } else if let Some(number) = strip_suffix(input, "shannon") {
+            (number, true)
+        } else if let Some(number) = strip_suffix(input, "ckb") {
+            (number, false)
+        } else {
+            (input, false)
+        };
+        let number = number
+            .trim()
+            .chars()
+            .filter(|c| *c != ',' && *c != '_')
+            .collect::<String>();
+        let is_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+        if is_shannons {
+            if number.is_empty() || !is_digits(&number) {
+                return Err(invalid());
+            }
+            number.parse::<u64>().map(Self).map_err(|_| invalid())
+        } else {
+            let mut parts = number.splitn(2, '.');
+            let integer = parts.next().unwrap_or_default();
+            let fraction = parts.next().unwrap_or_default();
+            if (integer.is_empty() && fraction.is_empty())
+                || fraction.len() > BYTE_DECIMALS
+                || !is_digits(integer)
+                || !is_digits(fraction)
+            {
+                return Err(invalid());
+            }
+            let integer = if integer.is_empty() {
+                0
+            } else {
+                integer.parse::<u64>().map_err(|_| invalid())?
+            };
+            let fraction = format!("{:0<width$}", fraction, width = BYTE_DECIMALS)
+                .parse::<u64>()
+                .map_err(|_| invalid())?;
+            Self::from_bytes(integer)? + Self(fraction)
+        }
+    }
+}
+
+fn strip_suffix<'a>(input: &'a str, suffix: &str) -> Option<&'a str> {
+    if input.len() < suffix.len() {
+        return None;
+    }
+    let index = input.len() - suffix.len();
+    if input.is_char_boundary(index) && input[index..].eq_ignore_ascii_case(suffix) {
+        Some(&input[..index])
+    } else {
+        None
+    }
+}
+
+impl Ratio {
+    pub fn new(numer: u64, denom: u64) -> Self {
+        Self { numer, denom }
+    }
+}
+
+impl ops::Add for Token {
+    type Output = Result<Self>;
+    fn add(self, rhs: Self) -> Self::Output {
+        self.0.checked_add(rhs.0).map(Self).ok_or_else(|| {
+            Error::from(DataErrorKind::TokenOverflow(format!(
+                "{} + {}",
+                self.0, rhs.0
+            )))
+        })
+    }
+}
+
+impl ops::Sub for Token {
+    type Output = Result<Self>;
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.0.checked_sub(rhs.0).map(Self).ok_or_else(|| {
+            Error::from(DataErrorKind::TokenOverflow(format!(
+                "{} - {}",
+                self.0, rhs.0
+            )))
+        })
+    }
+}
+
+impl ops::Mul<Ratio> for Token {
+    type Output = Result<Self>;
+    fn mul(self, rhs: Ratio) -> Self::Output {
+        if rhs.denom == 0 {
+            return Err(Error::from(DataErrorKind::TokenOverflow(format!(
+                "{} * {} / 0",
+                self.0, rhs.numer
+            ))));
+        }
+        let shannons = u128::from(self.0) * u128::from(rhs.numer) / u128::from(rhs.denom);
+        if shannons > u128::from(u64::max_value()) {
+            Err(Error::from(DataErrorKind::TokenOverflow(format!(
+                "{} * {} / {}",
+                self.0, rhs.numer, rhs.denom
+            ))))
+        } else {
+            Ok(Self(shannons as u64))
+        }
+    }
+}
+
+impl iter::Sum<Token> for Result<Token> {
+    fn sum<I: Iterator<Item = Token>>(mut iter: I) -> Self {
+        iter.try_fold(Token::zero(), |total, token| total + token)
+    }
+}
+
+impl<'a> iter::Sum<&'a Token> for Result<Token> {
+    fn sum<I: Iterator<Item = &'a Token>>(iter: I) -> Self {
+        iter.cloned().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<Token> {
+        s.parse()
+    }
+
+    #[test]
+    fn parse_amounts() {
+        for (input, shannons) in &[
+            ("61", 6_100_000_000),
+            ("61 CKB", 6_100_000_000),
+            ("1,048,617.12345678 CKB", 104_861_712_345_678),
+            ("1_000ckb", 100_000_000_000),
+            (".5", 50_000_000),
+            ("5.", 500_000_000),
+            ("0.00000001", 1),
+            ("100_000_000 shannons", 100_000_000),
+            ("1 shannon", 1),
+            ("  42 Shannons  ", 42),
+        ] {
+            assert_eq!(parse(input).unwrap().shannons(), *shannons, "{}", input);
+        }
+    }
+
+    #[test]
+    fn parse_invalid_amounts() {
+        for input in &[
+            "",
+            ".",
+            "CKB",
+            "shannons",
+            "0.123456789",
+            "1.5 shannons",
+            "-1",
+            "1e8",
+            "1.2.3",
+            "184467440738 CKB",
+            "18446744073709551616 shannons",
+        ] {
+            assert!(parse(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn display_amounts() {
+        for (shannons, output) in &[
+            (0, "0 CKB"),
+            (1, "0.00000001 CKB"),
+            (50_000_000, "0.5 CKB"),
+            (100_000_000_000, "1,000 CKB"),
+            (104_861_712_345_678, "1,048,617.12345678 CKB"),
+        ] {
+            assert_eq!(Token::from_shannons(*shannons).to_string(), *output);
+        }
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for shannons in &[
+            0,
+            1,
+            99_999_999,
+            100_000_000,
+            123_456_789_012,
+            u64::max_value(),
+        ] {
+            let token = Token::from_shannons(*shannons);
+            assert_eq!(parse(&token.to_string()).unwrap(), token);
+        }
+    }
+
+    #[test]
+    fn overflow() {
+        assert!(Token::from_bytes(u64::max_value()).is_err());
+        assert!((Token::from_shannons(u64::max_value()) + Token::from_shannons(1)).is_err());
+        assert!((Token::zero() - Token::from_shannons(1)).is_err());
+        assert!((Token::from_shannons(u64::max_value()) * Ratio::new(2, 1)).is_err());
+        assert!((Token::from_shannons(1) * Ratio::new(1, 0)).is_err());
+    }
 }
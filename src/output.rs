@@ -0,0 +1,270 @@
+// Copyright (C) 2019 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use parking_lot::{RwLock, RwLockWriteGuard};
+
+use crate::error::{Error, Result};
+
+/// The path which means the standard output.
+pub const STDOUT: &str = "-";
+
+pub enum Writer {
+    /// The content for the standard output is buffered, and printed when it is committed.
+    Stdout(Vec<u8>),
+    File(fs::File),
+}
+
+/// An output file which is written into a temporary file next to it, and the temporary file
+/// is renamed to the path only when the output is committed.
+///
+/// A failed run leaves neither an empty nor a truncated output, the temporary file is removed
+/// when an uncommitted output is dropped, and nothing is printed for the standard output.
+pub struct Output {
+    // No path for the standard output.
+    path: Option<PathBuf>,
+    temp: Option<PathBuf>,
+    writer: RwLock<Writer>,
+    committed: AtomicBool,
+}
+
+impl io::Write for Writer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Stdout(ref mut buffer) => buffer.write(buf),
+            Self::File(ref mut file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Stdout(_) => Ok(()),
+            Self::File(ref mut file) => file.flush(),
+        }
+    }
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.tmp", name, process::id()))
+}
+
+impl Output {
+    /// Creates an output, an existed path is an error unless `force` is set.
+    pub fn create(path_str: &str, force: bool) -> Result<Self> {
+        if path_str == STDOUT {
+            return Ok(Self {
+                path: None,
+                temp: None,
+                writer: RwLock::new(Writer::Stdout(Vec::new())),
+                committed: AtomicBool::new(false),
+            });
+        }
+        let path = Path::new(path_str);
+        if path.exists() && !force {
+            return Err(Error::OutputExisted(path_str.to_owned()));
+        }
+        let temp = temp_path(path);
+        let file = fs::OpenOptions::new()
+            .create_new(true)
+            .write(true)
+            .open(&temp)?;
+        Ok(Self {
+            path: Some(path.to_owned()),
+            temp: Some(temp),
+            writer: RwLock::new(Writer::File(file)),
+            committed: AtomicBool::new(false),
+        })
+    }
+
+    pub fn is_stdout(&self) -> bool {
+        self.path.is_none()
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<Writer> {
+        self.writer.write()
+    }
+
+    /// Flushes the output, and syncs the temporary file to the disk.
+    fn sync(&self) -> Result<()> {
+        let mut writer = self.writer.write();
+        io::Write::flush(&mut *writer)?;
+        if let Writer::File(ref file) = *writer {
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// Renames the temporary file to the path, or prints the buffered content for the standard
+    /// output; it does nothing if it is already committed.
+    pub fn commit(&self) -> Result<()> {
+        if self.committed.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        self.sync()?;
+        if let (Some(ref temp), Some(ref path)) = (&self.temp, &self.path) {
+            fs::rename(temp, path)?;
+            log::info!("saved {}", path.display());
+        } else if let Writer::Stdout(ref mut buffer) = *self.writer.write() {
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            io::Write::write_all(&mut stdout, buffer)?;
+            io::Write::flush(&mut stdout)?;
+            buffer.clear();
+        }
+        self.committed.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+impl Drop for Output {
+    fn drop(&mut self) {
+        if !self.committed.load(Ordering::SeqCst) {
+            if let Some(ref temp) = self.temp {
+                if let Err(err) = fs::remove_file(temp) {
+                    log::warn!("failed to remove {}: {}", temp.display(), err);
+                }
+            }
+        }
+    }
+}
+
+/// Commits all outputs, all of them are synced before any is renamed.
+pub fn commit_all(outputs: &[&Output]) -> Result<()> {
+    for output in outputs {
+        output.sync()?;
+    }
+    for output in outputs {
+        output.commit()?;
+    }
+    Ok(())
+}
+
+/// Checks that at most one output is the standard output.
+pub fn check_stdout(outputs: &[&Output]) -> Result<()> {
+    if outputs.iter().filter(|output| output.is_stdout()).count() > 1 {
+        Err(Error::StdoutConflict)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, io::Write as _};
+
+    use super::*;
+
+    /// A directory which is only used by one test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("ckb-gbgc-output-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut names = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn commit_renames_the_temporary_file() {
+        let dir = test_dir("commit");
+        let path = dir.join("spec.toml");
+        let output = Output::create(&path.to_string_lossy(), false).unwrap();
+        output.write().write_all(b"content").unwrap();
+        assert!(!path.exists());
+        assert_eq!(
+            entries(&dir),
+            vec![format!(".spec.toml.{}.tmp", process::id())]
+        );
+        output.commit().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "content");
+        // Committing again does nothing.
+        output.commit().unwrap();
+        drop(output);
+        assert_eq!(entries(&dir), vec!["spec.toml"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn drop_removes_the_uncommitted_temporary_file() {
+        let dir = test_dir("drop");
+        let path = dir.join("spec.toml");
+        let output = Output::create(&path.to_string_lossy(), false).unwrap();
+        output.write().write_all(b"content").unwrap();
+        drop(output);
+        assert!(entries(&dir).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn existed_outputs_require_force() {
+        let dir = test_dir("force");
+        let path = dir.join("spec.toml");
+        let path_str = path.to_string_lossy();
+        fs::write(&path, "old").unwrap();
+        match Output::create(&path_str, false) {
+            Err(Error::OutputExisted(existed)) => assert_eq!(existed, path_str),
+            _ => panic!("the existed output should not be replaced without --force"),
+        }
+        let output = Output::create(&path_str, true).unwrap();
+        output.write().write_all(b"new").unwrap();
+        // The old content is kept until the output is committed.
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        output.commit().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn temporary_files_are_not_shared() {
+        let dir = test_dir("conflict");
+        let path = dir.join("spec.toml");
+        let path_str = path.to_string_lossy();
+        let output = Output::create(&path_str, false).unwrap();
+        // The same temporary path in the same process is created only once.
+        match Output::create(&path_str, true) {
+            Err(Error::IO(err)) => assert_eq!(err.kind(), io::ErrorKind::AlreadyExists),
+            _ => panic!("the temporary file should be created only once"),
+        }
+        drop(output);
+        assert!(entries(&dir).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stdout_is_buffered_until_committed() {
+        let output = Output::create(STDOUT, false).unwrap();
+        assert!(output.is_stdout());
+        output.write().write_all(b"content").unwrap();
+        match *output.write() {
+            Writer::Stdout(ref buffer) => assert_eq!(&buffer[..], b"content"),
+            Writer::File(_) => panic!("should be the standard output"),
+        }
+        let another = Output::create(STDOUT, false).unwrap();
+        match check_stdout(&[&output, &another]) {
+            Err(Error::StdoutConflict) => {}
+            _ => panic!("only one output could be the standard output"),
+        }
+    }
+}
@@ -6,191 +6,248 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::collections::HashMap;
+use std::{collections::HashMap, str};
 
-use uckb_jsonrpc_client::interfaces::types::{prelude::Unpack, utilities, U256};
+use failure::Fail;
+
+use uckb_jsonrpc_client::interfaces::types::prelude::Unpack;
 
 use crate::{
-    arguments, client, constants, data,
-    error::{Error, Result},
-    module::{asset, config, hash, token},
+    arguments, audit, client, data,
+    error::{DataError, DataErrorKind, Error, Result},
+    module::{asset, config, hash, ledger, reward, token},
+    target,
 };
 
+/// The file of the errors in the chain config.
+const CHAIN_CONFIG: &str = "chain config";
+
+/// Only the primary reward counts for the round 5.3 mined.
+pub const ROUND_5_S3_REWARD_COMPONENTS: &[reward::Component] = &[reward::Component::Primary];
+/// The reward pool of the round 5.3 mined, in CKBytes.
+pub const ROUND_5_S3_REWARD_POOL: u64 = 18_000_000;
+/// The miners whose block rewards are less than it are not counted, in shannons.
+pub const ROUND_5_S3_LEAST_BLOCK_REWARD: u64 = 1_000;
+
+/// Splits the reward pool of the round 5.3 mined by the block rewards of the miners.
+///
+/// Returns the counted miners with their block rewards and their token rewards, the token
+/// rewards are in CKBytes and rounded down.
+pub fn split_round_5_s3_pool<K>(
+    block_rewards: Vec<(K, token::Token)>,
+) -> Result<Vec<(K, token::Token, u64)>> {
+    let reward_pool = token::Token::from_bytes(ROUND_5_S3_REWARD_POOL)?;
+    let least_block_reward = token::Token::from_shannons(ROUND_5_S3_LEAST_BLOCK_REWARD);
+    let counted = block_rewards
+        .into_iter()
+        .filter(|(_, block_reward)| *block_reward >= least_block_reward)
+        .collect::<Vec<_>>();
+    let total_block_reward = counted
+        .iter()
+        .map(|(_, block_reward)| *block_reward)
+        .sum::<Result<token::Token>>()?;
+    counted
+        .into_iter()
+        .map(|(key, block_reward)| {
+            let ratio = token::Ratio::new(block_reward.shannons(), total_block_reward.shannons());
+            let token_reward = (reward_pool * ratio)?.bytes();
+            Ok((key, block_reward, token_reward))
+        })
+        .collect()
+}
+
 pub fn process(
     args: &arguments::Arguments,
     chain_data: &client::ChainData,
     cfg: &config::Configuration,
-) -> Result<(Vec<config::Cell>, u32)> {
+    ledger: &mut ledger::Ledger,
+    audit: &mut audit::Audit,
+) -> Result<(Vec<config::Cell>, target::Report)> {
+    let chain = args.chain();
+    let scripts = &chain.scripts;
+    let network = args.network();
+    let rules = network.supply_rules();
     let mut cells = Vec::new();
+    let initial_total_supply = token::Token::from_shannons(chain.genesis.initial_total_supply);
 
     // Satoshi Gift
     let satoshi_cell = config::Cell {
-        capacity: constants::INITIAL_TOTAL_SUPPLY / 4,
+        capacity: (initial_total_supply * token::Ratio::new(1, 4))?.shannons(),
         lock: config::Lock {
-            code_hash: constants::SATOSHI_GIFT_CODE_HASH.to_owned(),
-            args: constants::SATOSHI_GIFT_ARGS.to_owned(),
+            code_hash: chain.satoshi_gift.code_hash.clone(),
+            args: chain.satoshi_gift.args.clone(),
             hash_type: "data".to_owned(),
         },
+        sources: vec![config::Source::new("chain config: satoshi_gift", None)],
     };
     log::info!("burned part = {}", satoshi_cell.capacity);
     cells.push(satoshi_cell);
 
     // Imported Part
     {
-        let imported_expected = (u128::from(constants::INITIAL_TOTAL_SUPPLY) * 725 / 1000) as u64;
-        let mut imported_cells = data::GENESIS_ALLOCATE
-            .lines()
-            .map(|line| {
-                let mut part = line.split(',');
-                let addr = part
-                    .next()
-                    .ok_or_else(|| Error::Unreachable(format!("split address from '{}'", line)))?;
-                let ckb = part
-                    .next()
-                    .ok_or_else(|| Error::Unreachable(format!("split ckb from '{}'", line)))?
-                    .parse::<u64>()?;
-                let date_opt = part.next();
-                if part.next().is_some() {
-                    Err(Error::Unreachable(format!(
-                        "'{}' has redundant fileds",
-                        line
-                    )))
-                } else {
-                    let hash = hash::extract_from_address_mainnet(addr)
-                        .transpose()?
+        let imported_expected = (initial_total_supply * token::Ratio::new(725, 1000))?;
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(data::GENESIS_ALLOCATE.as_bytes());
+        let mut imported_cells = reader
+            .records()
+            .map(|result| {
+                let file = data::GENESIS_ALLOCATE_PATH;
+                let record = read_record(file, result, None)?;
+                if record.len() < 2 || record.len() > 3 {
+                    let kind = DataErrorKind::FieldCount(3, record.len());
+                    return Err(DataError::from_record(file, &record, kind).into());
+                }
+                let addr = &record[0];
+                let token = parse_field::<token::Token>(file, &record, 1)?;
+                let date_opt = record.get(2);
+                let hash =
+                    hash::extract_from_address_with_prefixes(addr, network.address_prefixes())
+                        .transpose()
+                        .map_err(|err| {
+                            let kind = DataErrorKind::InvalidAddress(addr.to_owned());
+                            DataError::from_record(file, &record, kind).caused_by(err)
+                        })?
                         .ok_or_else(|| {
-                            Error::Unreachable(format!("parse mainnet address from '{}'", addr))
+                            let kind = DataErrorKind::InvalidAddress(addr.to_owned());
+                            DataError::from_record(file, &record, kind)
                         })?;
-                    let cell = if let Some(date) = date_opt {
-                        if date == "" || date == "\"\"" {
-                            asset::Owner::new_single(hash).with_bytes(ckb)
-                        } else {
-                            asset::Owner::new_multi(vec![hash], 0, 1, date, args.epoch())
-                                .map(|owner| owner.with_bytes(ckb))?
-                        }
+                let cell = if let Some(date) = date_opt {
+                    if date == "" || !rules.time_locks {
+                        asset::Owner::new_single(hash).with_token(token)
                     } else {
-                        asset::Owner::new_single(hash).with_bytes(ckb)
+                        asset::Owner::new_multi(vec![hash], 0, 1, date, args.epoch(), chain)
+                            .map(|owner| owner.with_token(token))
+                            .map_err(|err| {
+                                let kind = DataErrorKind::InvalidValue(date.to_owned());
+                                DataError::from_record(file, &record, kind)
+                                    .at_column(2)
+                                    .caused_by(err)
+                            })?
                     }
-                    .into_cell();
-                    Ok(cell)
+                } else {
+                    asset::Owner::new_single(hash).with_token(token)
                 }
+                .with_source(file, row_of(&record))
+                .into_cell(scripts);
+                Ok(cell)
             })
             .collect::<Result<Vec<_>>>()?;
-        let imported_actual = imported_cells.iter().map(|cell| cell.capacity).sum::<u64>();
+        let imported_actual = imported_cells
+            .iter()
+            .map(|cell| token::Token::from_shannons(cell.capacity))
+            .sum::<Result<token::Token>>()?;
         log::info!("imported part = {}", imported_actual);
-        if imported_expected != imported_actual {
-            return Err(Error::Unreachable(format!(
-                "imported capacity: expected: {}, actual: {}",
-                imported_expected, imported_actual
-            )));
-        }
+        audit.check_eq("imported part", imported_expected, imported_actual);
         cells.append(&mut imported_cells);
     }
 
     // Foundation Reserve
     let foundation_spent = {
-        let res = ckb_resource::Resource::bundled("specs/mainnet.toml".to_owned());
-        let spec = ckb_chain_spec::ChainSpec::load_from(&res).unwrap();
-        let genesis_block = spec.build_genesis().unwrap().data();
-        genesis_block
-            .as_reader()
+        let res = ckb_resource::Resource::bundled(network.reference_spec().to_owned());
+        let spec = ckb_chain_spec::ChainSpec::load_from(&res).map_err(|err| {
+            Error::Unreachable(format!("load the bundled {} spec: {}", network, err))
+        })?;
+        let genesis_block = spec
+            .build_genesis()
+            .map_err(|err| {
+                Error::Unreachable(format!("build the bundled {} genesis: {}", network, err))
+            })?
+            .data();
+        let genesis_block_reader = genesis_block.as_reader();
+        let tx = genesis_block_reader
             .transactions()
             .get(0)
-            .map(|tx| {
-                tx.raw()
-                    .outputs()
-                    .iter()
-                    .enumerate()
-                    .map(|(index, output)| {
-                        if index < 6 {
-                            output.capacity().unpack()
-                        } else {
-                            0u64
-                        }
-                    })
-                    .sum::<u64>()
-                    - tx.raw().outputs_data().get_unchecked(0).raw_data().len() as u64
-                        * token::BYTE_SHANNONS
-                    + cfg.message.as_bytes().len() as u64 * token::BYTE_SHANNONS
-            })
-            .ok_or_else(|| Error::Unreachable("compute foundation spent".to_owned()))?
+            .ok_or_else(|| Error::Unreachable("compute foundation spent".to_owned()))?;
+        // The issued cells follow the genesis cell and the system cells.
+        let spent_count = tx.raw().outputs().len() - spec.genesis.issued_cells.len();
+        let outputs = tx
+            .raw()
+            .outputs()
+            .iter()
+            .take(spent_count)
+            .map(|output| token::Token::from_shannons(output.capacity().unpack()))
+            .sum::<Result<token::Token>>()?;
+        let genesis_data = token::Token::from_bytes(
+            tx.raw().outputs_data().get_unchecked(0).raw_data().len() as u64,
+        )?;
+        let message = token::Token::from_bytes(cfg.message.as_bytes().len() as u64)?;
+        ((outputs - genesis_data)? + message)?
     };
     log::info!("foundation spent = {}", foundation_spent);
-    if foundation_spent != 1_264_963 * token::BYTE_SHANNONS {
-        return Err(Error::Unreachable(format!(
-            "foundation_spent(={}) should be 1_264_963 * 1_0000_0000",
-            foundation_spent
-        )));
+    if let Some(expected) = rules.foundation_spent {
+        audit.check_eq(
+            "foundation spent",
+            token::Token::from_bytes(expected)?,
+            foundation_spent,
+        );
     }
 
     {
-        let foundation_reserve = constants::INITIAL_TOTAL_SUPPLY * 2 / 100 - foundation_spent;
+        let foundation_reserve =
+            ((initial_total_supply * token::Ratio::new(2, 100))? - foundation_spent)?;
 
-        let foundation_cell = hash::extract_from_address_mainnet(constants::FOUNDATION_ADDR)
-            .ok_or_else(|| {
-                Error::Unreachable(format!(
-                    "parse mainnet address from '{}'",
-                    constants::FOUNDATION_ADDR
-                ))
-            })?
-            .and_then(|hash| {
-                asset::Owner::new_multi(vec![hash], 0, 1, constants::FOUNDATION_SINCE, args.epoch())
-                    .map(|owner| {
-                        log::trace!("foundation owner = {}", owner);
-                        owner.with_shannons(foundation_reserve)
-                    })
-            })
-            .map(asset::Asset::into_cell)?;
+        let foundation = &chain.foundation;
+        let hash = chain_address(args, "foundation.address", &foundation.address)?;
+        let foundation_cell = if !rules.time_locks {
+            asset::Owner::new_single(hash).with_token(foundation_reserve)
+        } else {
+            asset::Owner::new_multi(vec![hash], 0, 1, &foundation.since, args.epoch(), chain)
+                .map(|owner| {
+                    log::trace!("foundation owner = {}", owner);
+                    owner.with_token(foundation_reserve)
+                })
+                .map_err(|err| {
+                    let kind = DataErrorKind::InvalidValue(foundation.since.clone());
+                    DataError::new(CHAIN_CONFIG, kind).caused_by(err)
+                })?
+        }
+        .with_source("chain config: foundation.address", None)
+        .into_cell(scripts);
         log::info!("foundation part = {}", foundation_cell.capacity);
         cells.push(foundation_cell);
     }
 
-    let (assets_competition, remained, target) = process_competition(chain_data)?;
+    let (assets_competition, remained, report) =
+        process_competition(args, chain_data, ledger, audit)?;
     let mut competition_cells = assets_competition
         .into_iter()
-        .map(|x| x.into_cell())
+        .map(|x| x.into_cell(scripts))
         .collect::<Vec<_>>();
     cells.append(&mut competition_cells);
 
     // Testnet Remained
-    let testnet_cell = hash::extract_from_address_mainnet(constants::FOUNDATION_TESTNET_ADDR)
-        .ok_or_else(|| {
-            Error::Unreachable(format!(
-                "parse mainnet address from '{}'",
-                constants::FOUNDATION_TESTNET_ADDR
-            ))
-        })?
-        .map(|hash| {
-            asset::Owner::new_single(hash)
-                .with_shannons(remained)
-                .into_cell()
-        })?;
+    let testnet_address = &chain.foundation.testnet_address;
+    let testnet_hash = chain_address(args, "foundation.testnet_address", testnet_address)?;
+    let testnet_cell = asset::Owner::new_single(testnet_hash)
+        .with_token(remained)
+        .with_source("chain config: foundation.testnet_address", None)
+        .into_cell(scripts);
     log::info!("foundation testnet part = {}", testnet_cell.capacity);
     cells.push(testnet_cell);
 
-    let total_supply = cells.iter().map(|cell| cell.capacity).sum::<u64>() + foundation_spent;
-    if total_supply != constants::INITIAL_TOTAL_SUPPLY {
-        return Err(Error::Unreachable(format!(
-            "total supply: expected: {}, actual: {}",
-            constants::INITIAL_TOTAL_SUPPLY,
-            total_supply
-        )));
-    }
+    let total_supply = (cells
+        .iter()
+        .map(|cell| token::Token::from_shannons(cell.capacity))
+        .sum::<Result<token::Token>>()?
+        + foundation_spent)?;
+    audit.check_eq("total supply", initial_total_supply, total_supply);
 
-    Ok((cells, target))
+    Ok((cells, report))
 }
 
 macro_rules! assets_append {
     ($total:ident, $part:ident, $tag:literal) => {
         let reward = $part
             .iter()
-            .map(|asset| asset.token().shannons())
-            .sum::<u64>();
+            .map(|asset| asset.token())
+            .sum::<Result<token::Token>>()?;
         log::info!(
-            "        total reward for {}: {} accounts, {} ckb",
+            "        total reward for {}: {} accounts, {}",
             $tag,
             $part.len(),
-            reward / token::BYTE_SHANNONS
+            reward
         );
         if log::log_enabled!(log::Level::Debug) {
             for asset in $part.iter() {
@@ -201,482 +258,489 @@ macro_rules! assets_append {
     };
 }
 
-#[allow(clippy::cognitive_complexity)]
-fn process_competition(chain_data: &client::ChainData) -> Result<(Vec<asset::Asset>, u64, u32)> {
-    let mut expected_total_reward = 0u64;
-    let target;
+/// Extracts the hash from an address of the chain config.
+fn chain_address(args: &arguments::Arguments, field: &str, address: &str) -> Result<hash::H160> {
+    let prefixes = args.network().address_prefixes();
+    let kind = || {
+        let reason = format!("'{}' is not a {} address", address, args.network());
+        DataErrorKind::InvalidField(field.to_owned(), reason)
+    };
+    hash::extract_from_address_with_prefixes(address, prefixes)
+        .transpose()
+        .map_err(|err| DataError::new(CHAIN_CONFIG, kind()).caused_by(err))?
+        .ok_or_else(|| DataError::new(CHAIN_CONFIG, kind()).into())
+}
+
+/// The line of a CSV record in its file.
+fn row_of(record: &csv::StringRecord) -> Option<u64> {
+    record.position().map(csv::Position::line)
+}
+
+/// Reads a CSV record, and checks the count of its fields if `fields` is provided.
+fn read_record(
+    file: &str,
+    result: csv::Result<csv::StringRecord>,
+    fields: Option<usize>,
+) -> Result<csv::StringRecord> {
+    let record = result.map_err(|err| {
+        let kind = DataErrorKind::Unexpected("malformed record".to_owned());
+        let error = DataError::new(file, kind);
+        let error = if let Some(position) = err.position() {
+            error.at_line(position.line())
+        } else {
+            error
+        };
+        error.caused_by(err)
+    })?;
+    match fields {
+        Some(fields) if fields != record.len() => {
+            let kind = DataErrorKind::FieldCount(fields, record.len());
+            Err(DataError::from_record(file, &record, kind).into())
+        }
+        _ => Ok(record),
+    }
+}
+
+fn parse_field<T>(file: &str, record: &csv::StringRecord, column: usize) -> Result<T>
+where
+    T: str::FromStr,
+    T::Err: Fail,
+{
+    let value = record.get(column).ok_or_else(|| {
+        DataError::from_record(file, record, DataErrorKind::MissingField).at_column(column)
+    })?;
+    value.parse::<T>().map_err(|err| {
+        let kind = DataErrorKind::InvalidValue(value.to_owned());
+        DataError::from_record(file, record, kind)
+            .at_column(column)
+            .caused_by(err)
+            .into()
+    })
+}
+
+fn decode_or_skip<F>(
+    ledger: &mut ledger::Ledger,
+    file: &str,
+    record: &csv::StringRecord,
+    column: usize,
+    amount: token::Token,
+    decode: F,
+) -> Result<Option<hash::H160>>
+where
+    F: Fn(&str) -> Option<Result<hash::H160>>,
+{
+    let address = record.get(column).ok_or_else(|| {
+        DataError::from_record(file, record, DataErrorKind::MissingField).at_column(column)
+    })?;
+    if let Some(hash_result) = decode(address) {
+        hash_result.map(Some).map_err(|err| {
+            let kind = DataErrorKind::InvalidAddress(address.to_owned());
+            DataError::from_record(file, record, kind)
+                .at_column(column)
+                .caused_by(err)
+                .into()
+        })
+    } else {
+        ledger.skip(
+            file,
+            row_of(record),
+            address.to_owned(),
+            ledger::SkipReason::UndecodableAddress,
+            amount,
+        )?;
+        Ok(None)
+    }
+}
+
+/// A round whose reward pool is split by the block rewards of the miners.
+struct MinedRound {
+    name: &'static str,
+    file: &'static str,
+    content: &'static str,
+    /// The addresses and the token rewards are always in the first and the fourth columns.
+    block_reward_column: usize,
+    /// In CKBytes.
+    reward_pool: u64,
+    /// In shannons.
+    least_block_reward: u64,
+}
+
+fn process_mined_round<F>(
+    round: &MinedRound,
+    decode: F,
+    ledger: &mut ledger::Ledger,
+    audit: &mut audit::Audit,
+) -> Result<Vec<asset::Asset>>
+where
+    F: Fn(&str) -> Option<Result<hash::H160>>,
+{
+    // A single signature cell occupies 61 bytes.
+    let least_token_reward = config::occupied_bytes(20, 0);
+    let file = round.file;
+    let column = round.block_reward_column;
+    let reward_pool = token::Token::from_bytes(round.reward_pool)?;
+    let least_block_reward = token::Token::from_shannons(round.least_block_reward);
+    let mut assets = Vec::new();
+    let mut reader = csv::Reader::from_reader(round.content.as_bytes());
+    let mut counter = 0;
+    let mut total_block_reward = token::Token::zero();
+    let mut total_token_reward = token::Token::zero();
+    let mut check_data = Vec::new();
+    for result in reader.records() {
+        let record = read_record(file, result, Some(4))?;
+        counter += 1;
+        let block_reward = token::Token::from_shannons(parse_field::<u64>(file, &record, column)?);
+        if block_reward < least_block_reward {
+            let kind = DataErrorKind::BelowLimit(
+                least_block_reward.shannons().to_string(),
+                block_reward.shannons().to_string(),
+            );
+            let error = DataError::from_record(file, &record, kind).at_column(column);
+            return Err(error.into());
+        }
+        let token_reward = parse_field::<u64>(file, &record, 3)?;
+        if token_reward < least_token_reward {
+            let kind =
+                DataErrorKind::BelowLimit(least_token_reward.to_string(), token_reward.to_string());
+            let error = DataError::from_record(file, &record, kind).at_column(3);
+            return Err(error.into());
+        }
+        total_block_reward = (total_block_reward + block_reward)?;
+        total_token_reward = (total_token_reward + token::Token::from_bytes(token_reward)?)?;
+        check_data.push((block_reward, token_reward));
+        let hash = if let Some(hash) = decode_or_skip(
+            ledger,
+            file,
+            &record,
+            0,
+            token::Token::from_bytes(token_reward)?,
+            &decode,
+        )? {
+            hash
+        } else {
+            continue;
+        };
+        let asset = asset::Owner::new_single(hash)
+            .with_bytes(token_reward)?
+            .with_source(file, row_of(&record));
+        assets.push(asset);
+    }
+    let mut mismatched = 0;
+    for (block_reward, token_reward) in &check_data[..] {
+        let ratio = token::Ratio::new(block_reward.shannons(), total_block_reward.shannons());
+        let expected_token_reward = (reward_pool * ratio)?.bytes();
+        if expected_token_reward != *token_reward {
+            log::error!(
+                "        {}: expected token reward {}, actual {}",
+                round.name,
+                expected_token_reward,
+                token_reward
+            );
+            mismatched += 1;
+        }
+    }
+    audit.check_eq(
+        &format!("{}: proportional token rewards", round.name),
+        0,
+        mismatched,
+    );
+    audit.check_range(
+        &format!("{}: total token reward", round.name),
+        (reward_pool - token::Token::from_bytes(counter)?)?,
+        reward_pool,
+        total_token_reward,
+    );
+    Ok(assets)
+}
+
+fn process_competition(
+    args: &arguments::Arguments,
+    chain_data: &client::ChainData,
+    ledger: &mut ledger::Ledger,
+    audit: &mut audit::Audit,
+) -> Result<(Vec<asset::Asset>, token::Token, target::Report)> {
+    let prefix = &args.chain().source.address_prefix;
+    let mut expected_total_reward = token::Token::zero();
+    let report;
     let assets_total = {
-        let least_token_reward = 61;
         let mut assets_total = Vec::new();
         {
             let mut assets = Vec::new();
+            let file = data::ROUND_1_AWARDS_PATH;
             let mut reader = csv::Reader::from_reader(data::ROUND_1_AWARDS.as_bytes());
             for (index, result) in reader.records().enumerate() {
-                let record = result?;
-                if record.len() != 2 {
-                    return Err(Error::Unreachable(
-                        "round-1 awards record length".to_owned(),
-                    ));
-                }
-                let hash = {
-                    let addr_str = record.get(0).unwrap();
-                    if let Some(hash_result) = hash::deprecated::extract_from_address(addr_str) {
-                        hash_result?
-                    } else {
-                        continue;
-                    }
-                };
+                let record = read_record(file, result, Some(2))?;
                 let token = match index {
-                    0 => token::Token::from_bytes(200_000),
-                    1 => token::Token::from_bytes(100_000),
-                    2 => token::Token::from_bytes(60_000),
+                    0 => token::Token::from_bytes(200_000)?,
+                    1 => token::Token::from_bytes(100_000)?,
+                    2 => token::Token::from_bytes(60_000)?,
                     _ => {
-                        return Err(Error::Unreachable(
-                            "round 1 awards only 3 winners".to_owned(),
-                        ))
+                        let kind = DataErrorKind::Unexpected("only 3 winners".to_owned());
+                        return Err(DataError::from_record(file, &record, kind).into());
                     }
                 };
-                expected_total_reward += token.shannons();
-                let asset = asset::Owner::new_single(hash).with_token(token);
+                let hash = if let Some(hash) =
+                    decode_or_skip(ledger, file, &record, 0, token, |address| {
+                        hash::deprecated::extract_from_address(address, prefix)
+                    })? {
+                    hash
+                } else {
+                    continue;
+                };
+                expected_total_reward = (expected_total_reward + token)?;
+                let asset = asset::Owner::new_single(hash)
+                    .with_token(token)
+                    .with_source(file, row_of(&record));
                 assets.push(asset);
             }
             assets_append!(assets_total, assets, "round 1 awards");
         }
         {
             let mut assets = Vec::new();
+            let file = data::ROUND_1_LOTTERY_PATH;
             let mut reader = csv::Reader::from_reader(data::ROUND_1_LOTTERY.as_bytes());
             let mut counter = 0;
-            let lottery_reward = 10_000;
+            let lottery_reward = token::Token::from_bytes(10_000)?;
             for result in reader.records() {
-                let record = result?;
-                if record.len() != 2 {
-                    return Err(Error::Unreachable(
-                        "round-1 lottery record length".to_owned(),
-                    ));
-                }
+                let record = read_record(file, result, Some(2))?;
                 counter += 1;
-                let hash = {
-                    let addr_str = record.get(0).unwrap();
-                    if let Some(hash_result) = hash::deprecated::extract_from_address(addr_str) {
-                        hash_result?
-                    } else {
-                        continue;
-                    }
+                let hash = if let Some(hash) =
+                    decode_or_skip(ledger, file, &record, 0, lottery_reward, |address| {
+                        hash::deprecated::extract_from_address(address, prefix)
+                    })? {
+                    hash
+                } else {
+                    continue;
                 };
-                let asset = asset::Owner::new_single(hash).with_bytes(lottery_reward);
+                let asset = asset::Owner::new_single(hash)
+                    .with_token(lottery_reward)
+                    .with_source(file, row_of(&record));
                 assets.push(asset);
             }
-            if counter != 64 {
-                return Err(Error::Unreachable(
-                    "round 1 lottery only 64 winners".to_owned(),
-                ));
-            }
-            expected_total_reward += lottery_reward * 64 * token::BYTE_SHANNONS;
+            audit.check_eq("round-1 lottery: winners", 64, counter);
+            expected_total_reward =
+                (expected_total_reward + (lottery_reward * token::Ratio::new(64, 1))?)?;
             assets_append!(assets_total, assets, "round 1 lottery");
         }
         {
-            let mut assets = Vec::new();
-            let mut reader = csv::Reader::from_reader(data::ROUND_2_MINED.as_bytes());
-            let reward_pool = 2_000_000;
-            let least_block_reward = 4_000;
-            let mut counter = 0;
-            let mut total_block_reward = 0;
-            let mut total_token_reward = 0;
-            let mut check_data = Vec::new();
-            for result in reader.records() {
-                let record = result?;
-                if record.len() != 4 {
-                    return Err(Error::Unreachable("round-2 mined record length".to_owned()));
-                }
-                counter += 1;
-                let block_reward = record.get(1).unwrap().parse::<u64>().unwrap();
-                if block_reward < least_block_reward {
-                    return Err(Error::Unreachable(
-                        "round-2 mined block_reward < least_block_reward".to_owned(),
-                    ));
-                }
-                let token_reward = record.get(3).unwrap().parse::<u64>().unwrap();
-                if token_reward < least_token_reward {
-                    return Err(Error::Unreachable(
-                        "round-2 mined token_reward < least_token_reward".to_owned(),
-                    ));
-                }
-                total_block_reward += block_reward;
-                total_token_reward += token_reward;
-                check_data.push((block_reward, token_reward));
-                let hash = {
-                    let addr_str = record.get(0).unwrap();
-                    if let Some(hash_result) = hash::deprecated::extract_from_address(addr_str) {
-                        hash_result?
-                    } else {
-                        continue;
-                    }
-                };
-                let asset = asset::Owner::new_single(hash).with_bytes(token_reward);
-                assets.push(asset);
-            }
-            for (block_reward, token_reward) in &check_data[..] {
-                let expected_token_reward = u128::from(*block_reward) * u128::from(reward_pool)
-                    / u128::from(total_block_reward);
-                if expected_token_reward != u128::from(*token_reward) {
-                    return Err(Error::Unreachable(
-                        "round-2 mined expected_token_reward != token_reward".to_owned(),
-                    ));
-                }
-            }
-            if (reward_pool - counter) > total_token_reward || total_token_reward > reward_pool {
-                return Err(Error::Unreachable(
-                    "round-2 mined check total_token_reward".to_owned(),
-                ));
-            }
-            expected_total_reward += reward_pool * token::BYTE_SHANNONS;
+            let round = MinedRound {
+                name: "round-2 mined",
+                file: data::ROUND_2_MINED_PATH,
+                content: data::ROUND_2_MINED,
+                block_reward_column: 1,
+                reward_pool: 2_000_000,
+                least_block_reward: 4_000,
+            };
+            let mut assets = process_mined_round(
+                &round,
+                |address| hash::deprecated::extract_from_address(address, prefix),
+                ledger,
+                audit,
+            )?;
+            expected_total_reward =
+                (expected_total_reward + token::Token::from_bytes(round.reward_pool)?)?;
             assets_append!(assets_total, assets, "round 2 mined");
         }
         {
             let mut assets = Vec::new();
+            let file = data::ROUND_2_LUCKY_PATH;
             let mut reader = csv::Reader::from_reader(data::ROUND_2_LUCKY.as_bytes());
-            let reward_pool = 2_000_000;
+            let reward_pool = token::Token::from_bytes(2_000_000)?;
             let winners = 80;
+            let winner_reward = (reward_pool * token::Ratio::new(1, winners))?;
             let mut counter = 0;
             for result in reader.records() {
-                let record = result?;
-                if record.len() != 3 {
-                    return Err(Error::Unreachable("round-2 lucky record length".to_owned()));
-                }
-                let epoch = record.get(0).unwrap().parse::<u64>().unwrap();
+                let record = read_record(file, result, Some(3))?;
+                let epoch = parse_field::<u64>(file, &record, 0)?;
                 if epoch == 0 || epoch > winners {
                     continue;
                 }
                 counter += 1;
-                let hash = {
-                    let addr_str = record.get(1).unwrap();
-                    if let Some(hash_result) = hash::deprecated::extract_from_address(addr_str) {
-                        hash_result?
-                    } else {
-                        continue;
-                    }
+                let hash = if let Some(hash) =
+                    decode_or_skip(ledger, file, &record, 1, winner_reward, |address| {
+                        hash::deprecated::extract_from_address(address, prefix)
+                    })? {
+                    hash
+                } else {
+                    continue;
                 };
-                let asset = asset::Owner::new_single(hash).with_bytes(reward_pool / winners);
+                let asset = asset::Owner::new_single(hash)
+                    .with_token(winner_reward)
+                    .with_source(file, row_of(&record));
                 assets.push(asset);
             }
-            if counter != winners {
-                return Err(Error::Unreachable(format!(
-                    "count(={}) for winners(={}) is not match",
-                    counter, winners
-                )));
-            }
-            expected_total_reward += reward_pool * token::BYTE_SHANNONS;
+            audit.check_eq("round-2 lucky: winners", winners, counter);
+            expected_total_reward = (expected_total_reward + reward_pool)?;
             assets_append!(assets_total, assets, "round 2 lucky");
         }
         {
-            let mut assets = Vec::new();
-            let mut reader = csv::Reader::from_reader(data::ROUND_3_MINED.as_bytes());
-            let reward_pool = 3_000_000;
-            let least_block_reward = 3_000;
-            let mut counter = 0;
-            let mut total_block_reward = 0;
-            let mut total_token_reward = 0;
-            let mut check_data = Vec::new();
-            for result in reader.records() {
-                let record = result?;
-                if record.len() != 4 {
-                    return Err(Error::Unreachable("round-3 mined record length".to_owned()));
-                }
-                counter += 1;
-                let block_reward = record.get(2).unwrap().parse::<u64>().unwrap();
-                if block_reward < least_block_reward {
-                    return Err(Error::Unreachable(
-                        "round-3 mined block_reward < least_block_reward".to_owned(),
-                    ));
-                }
-                let token_reward = record.get(3).unwrap().parse::<u64>().unwrap();
-                if token_reward < least_token_reward {
-                    return Err(Error::Unreachable(
-                        "round-3 mined token_reward < least_token_reward".to_owned(),
-                    ));
-                }
-                total_block_reward += block_reward;
-                total_token_reward += token_reward;
-                check_data.push((block_reward, token_reward));
-                let hash = {
-                    let addr_str = record.get(0).unwrap();
-                    if let Some(hash_result) = hash::extract_from_address(addr_str) {
-                        hash_result?
-                    } else {
-                        continue;
-                    }
-                };
-                let asset = asset::Owner::new_single(hash).with_bytes(token_reward);
-                assets.push(asset);
-            }
-            for (block_reward, token_reward) in &check_data[..] {
-                let expected_token_reward = u128::from(*block_reward) * u128::from(reward_pool)
-                    / u128::from(total_block_reward);
-                if expected_token_reward != u128::from(*token_reward) {
-                    return Err(Error::Unreachable(
-                        "round-3 mined expected_token_reward != token_reward".to_owned(),
-                    ));
-                }
-            }
-            if (reward_pool - counter) > total_token_reward || total_token_reward > reward_pool {
-                return Err(Error::Unreachable(
-                    "round-3 mined check total_token_reward".to_owned(),
-                ));
-            }
-            expected_total_reward += reward_pool * token::BYTE_SHANNONS;
+            let round = MinedRound {
+                name: "round-3 mined",
+                file: data::ROUND_3_MINED_PATH,
+                content: data::ROUND_3_MINED,
+                block_reward_column: 2,
+                reward_pool: 3_000_000,
+                least_block_reward: 3_000,
+            };
+            let mut assets = process_mined_round(
+                &round,
+                |address| hash::extract_from_address(address, prefix),
+                ledger,
+                audit,
+            )?;
+            expected_total_reward =
+                (expected_total_reward + token::Token::from_bytes(round.reward_pool)?)?;
             assets_append!(assets_total, assets, "round 3 mined");
         }
         {
             let mut assets = Vec::new();
+            let file = data::ROUND_3_LUCKY_PATH;
             let mut reader = csv::Reader::from_reader(data::ROUND_3_LUCKY.as_bytes());
-            let reward_pool = 3_000_000;
+            let reward_pool = token::Token::from_bytes(3_000_000)?;
             let winners = 80;
+            let winner_reward = (reward_pool * token::Ratio::new(1, winners))?;
             let mut counter = 0;
             for result in reader.records() {
-                let record = result?;
-                if record.len() != 3 {
-                    return Err(Error::Unreachable("round-3 lucky record length".to_owned()));
-                }
-                let epoch = record.get(0).unwrap().parse::<u64>().unwrap();
+                let record = read_record(file, result, Some(3))?;
+                let epoch = parse_field::<u64>(file, &record, 0)?;
                 if epoch == 0 || epoch > winners {
                     continue;
                 }
                 counter += 1;
-                let hash = {
-                    let addr_str = record.get(1).unwrap();
-                    if let Some(hash_result) = hash::extract_from_address(addr_str) {
-                        hash_result?
-                    } else {
-                        continue;
-                    }
+                let hash = if let Some(hash) =
+                    decode_or_skip(ledger, file, &record, 1, winner_reward, |address| {
+                        hash::extract_from_address(address, prefix)
+                    })? {
+                    hash
+                } else {
+                    continue;
                 };
-                let asset = asset::Owner::new_single(hash).with_bytes(reward_pool / winners);
+                let asset = asset::Owner::new_single(hash)
+                    .with_token(winner_reward)
+                    .with_source(file, row_of(&record));
                 assets.push(asset);
             }
-            if counter != winners {
-                return Err(Error::Unreachable(format!(
-                    "count(={}) for winners(={}) is not match",
-                    counter, winners
-                )));
-            }
-            expected_total_reward += reward_pool * token::BYTE_SHANNONS;
+            audit.check_eq("round-3 lucky: winners", winners, counter);
+            expected_total_reward = (expected_total_reward + reward_pool)?;
             assets_append!(assets_total, assets, "round 3 lucky");
         }
         {
-            let mut assets = Vec::new();
-            let mut reader = csv::Reader::from_reader(data::ROUND_4_MINED.as_bytes());
-            let reward_pool = 9_000_000;
-            let least_block_reward = 1_000;
-            let mut counter = 0;
-            let mut total_block_reward = 0;
-            let mut total_token_reward = 0;
-            let mut check_data = Vec::new();
-            for result in reader.records() {
-                let record = result?;
-                if record.len() != 4 {
-                    return Err(Error::Unreachable("round-4 mined record length".to_owned()));
-                }
-                counter += 1;
-                let block_reward = record.get(1).unwrap().parse::<u64>().unwrap();
-                if block_reward < least_block_reward {
-                    return Err(Error::Unreachable(
-                        "round-4 mined block_reward < least_block_reward".to_owned(),
-                    ));
-                }
-                let token_reward = record.get(3).unwrap().parse::<u64>().unwrap();
-                if token_reward < least_token_reward {
-                    return Err(Error::Unreachable(
-                        "round-4 mined token_reward < least_token_reward".to_owned(),
-                    ));
-                }
-                total_block_reward += block_reward;
-                total_token_reward += token_reward;
-                check_data.push((block_reward, token_reward));
-                let hash = {
-                    let addr_str = record.get(0).unwrap();
-                    if let Some(hash_result) = hash::extract_from_address(addr_str) {
-                        hash_result?
-                    } else {
-                        continue;
-                    }
-                };
-                let asset = asset::Owner::new_single(hash).with_bytes(token_reward);
-                assets.push(asset);
-            }
-            for (block_reward, token_reward) in &check_data[..] {
-                let expected_token_reward = u128::from(*block_reward) * u128::from(reward_pool)
-                    / u128::from(total_block_reward);
-                if expected_token_reward != u128::from(*token_reward) {
-                    return Err(Error::Unreachable(
-                        "round-4 mined expected_token_reward != token_reward".to_owned(),
-                    ));
-                }
-            }
-            if (reward_pool - counter) > total_token_reward || total_token_reward > reward_pool {
-                return Err(Error::Unreachable(
-                    "round-4 mined check total_token_reward".to_owned(),
-                ));
-            }
-            expected_total_reward += reward_pool * token::BYTE_SHANNONS;
+            let round = MinedRound {
+                name: "round-4 mined",
+                file: data::ROUND_4_MINED_PATH,
+                content: data::ROUND_4_MINED,
+                block_reward_column: 1,
+                reward_pool: 9_000_000,
+                least_block_reward: 1_000,
+            };
+            let mut assets = process_mined_round(
+                &round,
+                |address| hash::extract_from_address(address, prefix),
+                ledger,
+                audit,
+            )?;
+            expected_total_reward =
+                (expected_total_reward + token::Token::from_bytes(round.reward_pool)?)?;
             assets_append!(assets_total, assets, "round 4 mined");
         }
         {
-            let mut assets = Vec::new();
-            let mut reader = csv::Reader::from_reader(data::ROUND_5_S1_MINED.as_bytes());
-            let reward_pool = 12_000_000;
-            let least_block_reward = 1_000;
-            let mut counter = 0;
-            let mut total_block_reward = 0;
-            let mut total_token_reward = 0;
-            let mut check_data = Vec::new();
-            for result in reader.records() {
-                let record = result?;
-                if record.len() != 4 {
-                    return Err(Error::Unreachable(
-                        "round-5.1 mined record length".to_owned(),
-                    ));
-                }
-                counter += 1;
-                let block_reward = record.get(2).unwrap().parse::<u64>().unwrap();
-                if block_reward < least_block_reward {
-                    return Err(Error::Unreachable(
-                        "round-5.1 block_reward < least_block_reward".to_owned(),
-                    ));
-                }
-                let token_reward = record.get(3).unwrap().parse::<u64>().unwrap();
-                if token_reward < least_token_reward {
-                    return Err(Error::Unreachable(
-                        "round-5.1 token_reward < least_token_reward".to_owned(),
-                    ));
-                }
-                total_block_reward += block_reward;
-                total_token_reward += token_reward;
-                check_data.push((block_reward, token_reward));
-                let hash = {
-                    let addr_str = record.get(0).unwrap();
-                    if let Some(hash_result) = hash::extract_from_address(addr_str) {
-                        hash_result?
-                    } else {
-                        continue;
-                    }
-                };
-                let asset = asset::Owner::new_single(hash).with_bytes(token_reward);
-                assets.push(asset);
-            }
-            for (block_reward, token_reward) in &check_data[..] {
-                let expected_token_reward = u128::from(*block_reward) * u128::from(reward_pool)
-                    / u128::from(total_block_reward);
-                if expected_token_reward != u128::from(*token_reward) {
-                    return Err(Error::Unreachable(
-                        "round-5.1 expected_token_reward != token_reward".to_owned(),
-                    ));
-                }
-            }
-            if (reward_pool - counter) > total_token_reward || total_token_reward > reward_pool {
-                return Err(Error::Unreachable(
-                    "round-5.1 check total_token_reward".to_owned(),
-                ));
-            }
-            expected_total_reward += reward_pool * token::BYTE_SHANNONS;
+            let round = MinedRound {
+                name: "round-5.1 mined",
+                file: data::ROUND_5_S1_MINED_PATH,
+                content: data::ROUND_5_S1_MINED,
+                block_reward_column: 2,
+                reward_pool: 12_000_000,
+                least_block_reward: 1_000,
+            };
+            let mut assets = process_mined_round(
+                &round,
+                |address| hash::extract_from_address(address, prefix),
+                ledger,
+                audit,
+            )?;
+            expected_total_reward =
+                (expected_total_reward + token::Token::from_bytes(round.reward_pool)?)?;
             assets_append!(assets_total, assets, "round 5.1 mined");
         }
         {
-            let mut assets = Vec::new();
-            let mut reader = csv::Reader::from_reader(data::ROUND_5_S2_MINED.as_bytes());
-            let reward_pool = 15_000_000;
-            let least_block_reward = 1_000;
-            let mut counter = 0;
-            let mut total_block_reward = 0;
-            let mut total_token_reward = 0;
-            let mut check_data = Vec::new();
-            for result in reader.records() {
-                let record = result?;
-                if record.len() != 4 {
-                    return Err(Error::Unreachable(
-                        "round-5.2 mined record length".to_owned(),
-                    ));
-                }
-                counter += 1;
-                let block_reward = record.get(2).unwrap().parse::<u64>().unwrap();
-                if block_reward < least_block_reward {
-                    return Err(Error::Unreachable(
-                        "round-5.2 block_reward < least_block_reward".to_owned(),
-                    ));
-                }
-                let token_reward = record.get(3).unwrap().parse::<u64>().unwrap();
-                if token_reward < least_token_reward {
-                    return Err(Error::Unreachable(
-                        "round-5.2 token_reward < least_token_reward".to_owned(),
-                    ));
-                }
-                total_block_reward += block_reward;
-                total_token_reward += token_reward;
-                check_data.push((block_reward, token_reward));
-                let hash = {
-                    let addr_str = record.get(0).unwrap();
-                    if let Some(hash_result) = hash::extract_from_address(addr_str) {
-                        hash_result?
-                    } else {
-                        continue;
-                    }
-                };
-                let asset = asset::Owner::new_single(hash).with_bytes(token_reward);
-                assets.push(asset);
-            }
-            for (block_reward, token_reward) in &check_data[..] {
-                let expected_token_reward = u128::from(*block_reward) * u128::from(reward_pool)
-                    / u128::from(total_block_reward);
-                if expected_token_reward != u128::from(*token_reward) {
-                    return Err(Error::Unreachable(
-                        "round-5.2 expected_token_reward != token_reward".to_owned(),
-                    ));
-                }
-            }
-            if (reward_pool - counter) > total_token_reward || total_token_reward > reward_pool {
-                return Err(Error::Unreachable(
-                    "round-5.2 check total_token_reward".to_owned(),
-                ));
-            }
-            expected_total_reward += reward_pool * token::BYTE_SHANNONS;
+            let round = MinedRound {
+                name: "round-5.2 mined",
+                file: data::ROUND_5_S2_MINED_PATH,
+                content: data::ROUND_5_S2_MINED,
+                block_reward_column: 2,
+                reward_pool: 15_000_000,
+                least_block_reward: 1_000,
+            };
+            let mut assets = process_mined_round(
+                &round,
+                |address| hash::extract_from_address(address, prefix),
+                ledger,
+                audit,
+            )?;
+            expected_total_reward =
+                (expected_total_reward + token::Token::from_bytes(round.reward_pool)?)?;
             assets_append!(assets_total, assets, "round 5.2 mined");
         }
         {
             let mut assets = Vec::new();
-            let reward_pool = 18_000_000u64;
-            let least_block_reward = 1_000;
+            let reward_pool = token::Token::from_bytes(ROUND_5_S3_REWARD_POOL)?;
             let mut counter = 0;
-            let mut total_token_reward = 0;
-            let r5s3_data = chain_data
-                .rewards()
-                .iter()
-                .filter(|(_, block_reward)| **block_reward >= least_block_reward)
-                .collect::<HashMap<_, _>>();
-            let total_block_reward = r5s3_data.values().cloned().sum::<u64>();
-            for (slice, block_reward) in r5s3_data {
+            let mut total_token_reward = token::Token::zero();
+            let chain_rewards = chain_data.rewards(ROUND_5_S3_REWARD_COMPONENTS)?;
+            let r5s3_data = split_round_5_s3_pool(
+                chain_rewards
+                    .iter()
+                    .map(|(lock, block_reward)| (lock, *block_reward))
+                    .collect(),
+            )?;
+            for (lock, _, token_reward) in r5s3_data {
                 counter += 1;
-                if let Some(hash) = hash::extract_from_slice(&slice) {
-                    let token_reward = ((u128::from(*block_reward)
-                        * u128::from(reward_pool * token::BYTE_SHANNONS)
-                        / u128::from(total_block_reward))
-                        as u64)
-                        / token::BYTE_SHANNONS;
-                    total_token_reward += token_reward;
-                    let asset = asset::Owner::new_single(hash).with_bytes(token_reward);
+                let owner =
+                    if let Some(owner) = asset::Owner::from_lock(lock, &args.chain().scripts)? {
+                        Some(owner)
+                    } else if let Some(fallback) = args.fallback() {
+                        ledger.redirect(
+                            data::ROUND_5_S3_MINED_PATH,
+                            None,
+                            lock.args.clone(),
+                            ledger::SkipReason::of_lock(lock, &args.chain().scripts),
+                            token::Token::from_bytes(token_reward)?,
+                            fallback.to_string(),
+                        );
+                        Some(fallback.to_owned())
+                    } else {
+                        None
+                    };
+                if let Some(owner) = owner {
+                    total_token_reward =
+                        (total_token_reward + token::Token::from_bytes(token_reward)?)?;
+                    let asset = owner
+                        .with_bytes(token_reward)?
+                        .with_source(data::ROUND_5_S3_MINED_PATH, None);
                     assets.push(asset);
+                } else {
+                    ledger.skip(
+                        data::ROUND_5_S3_MINED_PATH,
+                        None,
+                        lock.args.clone(),
+                        ledger::SkipReason::of_lock(lock, &args.chain().scripts),
+                        token::Token::from_bytes(token_reward)?,
+                    )?;
                 }
             }
-            if (reward_pool - counter) > total_token_reward || total_token_reward > reward_pool {
-                return Err(Error::Unreachable(
-                    "round-5.3 mined check total_token_reward".to_owned(),
-                ));
-            }
-            expected_total_reward += reward_pool * token::BYTE_SHANNONS;
+            audit.check_range(
+                "round-5.3 mined: total token reward",
+                (reward_pool - token::Token::from_bytes(counter)?)?,
+                reward_pool,
+                total_token_reward,
+            );
+            expected_total_reward = (expected_total_reward + reward_pool)?;
             assets_append!(assets_total, assets, "round 5.3 mined");
 
-            let diff = chain_data.diff_avg() * U256::from(3u8) / U256::from(2u8)
-                * U256::from(chain_data.rewards().values().sum::<u64>())
-                / U256::from(reward_pool * token::BYTE_SHANNONS);
-            target = utilities::difficulty_to_compact(diff);
+            let chain_total_reward = chain_rewards.values().sum::<Result<token::Token>>()?;
+            report =
+                args.target()
+                    .calculate(chain_data.epochs(), chain_total_reward, reward_pool)?;
         }
         assets_total
     };
@@ -684,34 +748,54 @@ fn process_competition(chain_data: &client::ChainData) -> Result<(Vec<asset::Ass
     let assets_ordered = {
         let mut assets_unique = HashMap::new();
         for asset in assets_total.iter() {
-            let shannons = assets_unique.entry(asset.owner()).or_insert(0);
-            *shannons += asset.token().shannons();
+            let (token, sources) = assets_unique
+                .entry(asset.owner())
+                .or_insert_with(|| (token::Token::zero(), Vec::new()));
+            *token = (*token + *asset.token())?;
+            sources.extend_from_slice(asset.sources());
         }
         let mut assets = Vec::new();
-        for (owner, shannons) in assets_unique.into_iter() {
-            assets.push(owner.to_owned().with_shannons(shannons));
+        for (owner, (token, sources)) in assets_unique.into_iter() {
+            let asset = owner.to_owned().with_token(token).with_sources(sources);
+            assets.push(asset);
         }
         assets.sort_by(|ref a, ref b| a.owner().cmp(b.owner()));
         assets
     };
     let total_reward = assets_ordered
         .iter()
-        .map(|asset| asset.token().shannons())
-        .sum::<u64>();
+        .map(|asset| asset.token())
+        .sum::<Result<token::Token>>()?;
     log::info!("    testnet assets unique = {}", assets_ordered.len());
     log::info!(
         "    testnet expected total reward = {}",
         expected_total_reward
     );
     log::info!("    testnet   actual total reward = {}", total_reward);
-    if expected_total_reward < total_reward {
-        return Err(Error::Unreachable(format!(
-            "expected_total_reward(={}) < total_reward(={})",
-            expected_total_reward, total_reward
-        )));
-    }
-    let remained = constants::INITIAL_TOTAL_SUPPLY / 200 - total_reward;
+    audit.check_le("testnet total reward", expected_total_reward, total_reward);
+    let testnet_part = (token::Token::from_shannons(args.chain().genesis.initial_total_supply)
+        * token::Ratio::new(1, 200))?;
+    // An overdrawn testnet part fails when the audit is finished, after the audit is saved.
+    let remained = if audit.check_le("testnet part", testnet_part, total_reward) {
+        (testnet_part - total_reward)?
+    } else {
+        token::Token::zero()
+    };
     log::info!("    testnet remained tokens = {}", remained);
+    if ledger.count_forfeited() > 0 {
+        log::warn!(
+            "    skipped {} entries, {} forfeited into testnet remained",
+            ledger.count_forfeited(),
+            ledger.total_forfeited()?
+        );
+    }
+    if ledger.count_redirected() > 0 {
+        log::warn!(
+            "    redirected {} entries, {} paid to the fallback address",
+            ledger.count_redirected(),
+            ledger.total_redirected()?
+        );
+    }
 
-    Ok((assets_ordered, remained, target))
+    Ok((assets_ordered, remained, report))
 }
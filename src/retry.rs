@@ -0,0 +1,117 @@
+// Copyright (C) 2019 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{future::Future, time};
+
+use failure::Fail;
+use property::Property;
+use rand::Rng;
+
+use crate::error::RpcError;
+
+#[derive(Debug, Fail)]
+#[fail(display = "timed out after {:?}", _0)]
+pub struct Timeout(time::Duration);
+
+/// Classifies the errors of an attempt.
+pub trait Retryable {
+    /// Whether another attempt could succeed, such as after a transport error.
+    ///
+    /// An error which the server replied on purpose fails immediately, since the same
+    /// request always gets the same reply.
+    fn is_retryable(&self) -> bool;
+}
+
+#[derive(Debug, Clone, Copy, Property)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_delay: time::Duration,
+    max_delay: time::Duration,
+    timeout: time::Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_delay: time::Duration::from_millis(500),
+            max_delay: time::Duration::from_secs(30),
+            timeout: time::Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, timeout: time::Duration) -> Self {
+        Self {
+            max_attempts,
+            timeout,
+            ..Default::default()
+        }
+    }
+
+    /// The exponential backoff with jitter before the next attempt.
+    ///
+    /// The delay is doubled for each attempt and capped by `max_delay`, then a
+    /// random value in its upper half is chosen.
+    pub fn delay(&self, attempt: u32) -> time::Duration {
+        let exp = attempt.saturating_sub(1).min(16);
+        let delay = self
+            .initial_delay
+            .checked_mul(1 << exp)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+        let millis = delay.as_millis() as u64;
+        let jittered = rand::thread_rng().gen_range(millis / 2, millis + 1);
+        time::Duration::from_millis(jittered)
+    }
+}
+
+/// Calls `action` until it succeeds, each attempt is limited by the timeout of the policy.
+///
+/// Timed out attempts are always retried, other errors are retried only if they are
+/// retryable. Returns the error of the last attempt if all attempts failed.
+pub async fn retry<T, E, F, Fut>(
+    policy: RetryPolicy,
+    method: &'static str,
+    block: Option<u64>,
+    mut action: F,
+) -> Result<T, RpcError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Fail + Retryable,
+{
+    let mut attempt = 1;
+    loop {
+        let error = match tokio::time::timeout(policy.timeout, action()).await {
+            Ok(Ok(item)) => return Ok(item),
+            Ok(Err(err)) if !err.is_retryable() => {
+                let error = RpcError::new(method, block, err);
+                log::error!("{}, not retryable", error);
+                return Err(error);
+            }
+            Ok(Err(err)) => RpcError::new(method, block, err),
+            Err(_) => RpcError::new(method, block, Timeout(policy.timeout)),
+        };
+        if attempt >= policy.max_attempts {
+            log::error!("{}, gave up after {} attempts", error, attempt);
+            return Err(error);
+        }
+        let delay = policy.delay(attempt);
+        log::warn!(
+            "{} (attempt {}/{}), retry in {:?}",
+            error,
+            attempt,
+            policy.max_attempts,
+            delay
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
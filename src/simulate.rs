@@ -0,0 +1,350 @@
+// Copyright (C) 2019 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+
+use serde_derive::Serialize;
+
+use uckb_jsonrpc_client::interfaces::types::{utilities, U256};
+
+use crate::{
+    arguments, client, constants,
+    error::{Error, Result},
+    module::chain::Consensus,
+    target,
+};
+
+/// How the launch would go, judged by the expected block time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    Fast,
+    Normal,
+    Slow,
+}
+
+/// An expected value with its 95% confidence interval.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Range {
+    expected: f64,
+    lower: f64,
+    upper: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct EpochRow {
+    number: u64,
+    compact_target: String,
+    // Hashes per second.
+    hash_rate: f64,
+}
+
+/// The simulated launch, all times are in seconds.
+#[derive(Debug, Serialize)]
+pub struct Simulation {
+    compact_target: String,
+    epochs: Vec<EpochRow>,
+    hash_rate: Range,
+    block_time: Range,
+    epoch_length: u64,
+    first_epoch_duration: Range,
+    epoch_duration_target: u64,
+    min_block_interval: u64,
+    max_block_interval: u64,
+    verdict: Verdict,
+}
+
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:.2} (95% confidence: {:.2} ~ {:.2})",
+            self.expected, self.lower, self.upper
+        )
+    }
+}
+
+impl Range {
+    /// The interval of `CONFIDENCE_Z` standard deviations around the expected value, a
+    /// negative lower bound is clamped to zero.
+    fn new(expected: f64, std_dev: f64) -> Self {
+        let spread = constants::CONFIDENCE_Z * std_dev;
+        Self {
+            expected,
+            lower: (expected - spread).max(0.0),
+            upper: expected + spread,
+        }
+    }
+}
+
+/// The difficulty in float, the precision is enough for the estimations.
+fn difficulty_to_f64(difficulty: &U256) -> f64 {
+    difficulty
+        .to_le_bytes()
+        .iter()
+        .rev()
+        .fold(0.0, |value, byte| value * 256.0 + f64::from(*byte))
+}
+
+fn compact_to_difficulty(compact: u32) -> Result<f64> {
+    let difficulty = difficulty_to_f64(&utilities::compact_to_difficulty(compact));
+    if difficulty > 0.0 {
+        Ok(difficulty)
+    } else {
+        Err(Error::InvalidCompactTarget(format!("{:#x}", compact)))
+    }
+}
+
+/// Estimates the launch by the hash rate of the observed epochs.
+///
+/// The hash rate is the total work divided by the total duration. Its standard error is the
+/// standard deviation of the epoch hash rates divided by `sqrt(n)`; for a single epoch, the
+/// deviation is the randomness of mining alone, `1 / sqrt(length)` of the hash rate.
+///
+/// The block time is the difficulty divided by the hash rate, so it has the same relative
+/// error. The first epoch lasts for a sum of `epoch_length` exponential block times, its
+/// variance is the variance of mining, `length * block_time^2`, plus the error of the block
+/// time, `(length * block_time_error)^2`.
+pub fn simulate(
+    epochs: &[target::Epoch],
+    compact: u32,
+    epoch_length: u64,
+    consensus: &Consensus,
+) -> Result<Simulation> {
+    let mut rows = Vec::with_capacity(epochs.len());
+    let mut total_work = 0.0;
+    let mut total_duration = 0.0;
+    for epoch in epochs {
+        let work = difficulty_to_f64(&epoch.difficulty()) * epoch.length() as f64;
+        let duration = epoch.duration() as f64 / 1000.0;
+        if duration <= 0.0 {
+            return Err(target::epochs_error(format!(
+                "epoch {} has no duration",
+                epoch.number()
+            )));
+        }
+        let hash_rate = work / duration;
+        total_work += work;
+        total_duration += duration;
+        rows.push(EpochRow {
+            number: epoch.number(),
+            compact_target: format!("{:#x}", epoch.compact_target()),
+            hash_rate,
+        });
+    }
+    if rows.is_empty() {
+        return Err(target::epochs_error("no epochs to simulate".to_owned()));
+    }
+    let expected_rate = total_work / total_duration;
+    let count = rows.len() as f64;
+    let rate_error = if rows.len() > 1 {
+        let squares = rows
+            .iter()
+            .map(|row| (row.hash_rate - expected_rate).powi(2))
+            .sum::<f64>();
+        (squares / (count - 1.0)).sqrt() / count.sqrt()
+    } else {
+        expected_rate / (epochs[0].length().max(1) as f64).sqrt()
+    };
+    let hash_rate = Range::new(expected_rate, rate_error);
+
+    let difficulty = compact_to_difficulty(compact)?;
+    let expected_time = difficulty / expected_rate;
+    let time_error = expected_time * rate_error / expected_rate;
+    let block_time = Range::new(expected_time, time_error);
+    let length = epoch_length as f64;
+    let first_epoch_duration = Range::new(
+        length * expected_time,
+        (length * expected_time.powi(2) + (length * time_error).powi(2)).sqrt(),
+    );
+    let verdict = if block_time.expected < consensus.min_block_interval as f64 {
+        Verdict::Fast
+    } else if block_time.expected > consensus.max_block_interval as f64 {
+        Verdict::Slow
+    } else {
+        Verdict::Normal
+    };
+    Ok(Simulation {
+        compact_target: format!("{:#x}", compact),
+        epochs: rows,
+        hash_rate,
+        block_time,
+        epoch_length,
+        first_epoch_duration,
+        epoch_duration_target: consensus.epoch_duration_target,
+        min_block_interval: consensus.min_block_interval,
+        max_block_interval: consensus.max_block_interval,
+        verdict,
+    })
+}
+
+impl Simulation {
+    /// Prints the simulation, a slow or fast launch is warned.
+    pub fn log(&self) {
+        log::info!(
+            "simulate the launch with compact target {}",
+            self.compact_target
+        );
+        for epoch in &self.epochs {
+            log::info!(
+                "    epoch {}: compact target {}, hash rate {:.2} H/s",
+                epoch.number,
+                epoch.compact_target,
+                epoch.hash_rate
+            );
+        }
+        log::info!("    hash rate = {} H/s", self.hash_rate);
+        log::info!("    block time = {} s", self.block_time);
+        log::info!(
+            "    first epoch ({} blocks) = {} s, target is {} s",
+            self.epoch_length,
+            self.first_epoch_duration,
+            self.epoch_duration_target
+        );
+        let min = self.min_block_interval as f64;
+        let max = self.max_block_interval as f64;
+        match self.verdict {
+            Verdict::Fast => log::warn!(
+                "    a fast launch is likely, the expected block time is less than {} s",
+                min
+            ),
+            Verdict::Slow => log::warn!(
+                "    a slow launch is likely, the expected block time is greater than {} s",
+                max
+            ),
+            Verdict::Normal => {
+                if self.block_time.lower < min || self.block_time.upper > max {
+                    log::warn!(
+                        "    the block time could be out of [{}, {}] s in the 95% confidence",
+                        min,
+                        max
+                    );
+                } else {
+                    log::info!("    the launch is normal");
+                }
+            }
+        }
+    }
+}
+
+pub fn run(args: &arguments::SimulateArguments) -> Result<()> {
+    let numbers = (0..args.window())
+        .rev()
+        .map(|i| args.epoch() - i)
+        .collect::<Vec<_>>();
+    log::info!(
+        "fetch epochs [{}, {}] ...",
+        numbers[0],
+        numbers[numbers.len() - 1]
+    );
+    let epochs = client::fetch_epochs(args.rpc(), args.chain(), &numbers)?;
+    let simulation = simulate(
+        &epochs,
+        args.target(),
+        args.epoch_length(),
+        &args.chain().consensus,
+    )?;
+    simulation.log();
+    if let Some(file) = args.output() {
+        serde_json::to_writer_pretty(&mut *file.write(), &simulation)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::CONFIDENCE_Z;
+
+    const COMPACT: u32 = 0x1a08_a97e;
+
+    fn consensus() -> Consensus {
+        Consensus {
+            confirmations: 11,
+            epoch_avg_count: 1,
+            planned_epoch: 89,
+            epoch_duration_target: 14_400,
+            min_block_interval: 8,
+            max_block_interval: 48,
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() <= expected.abs() * 1e-9,
+            "expected {}, actual {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn estimate_of_one_epoch() {
+        // 1000 blocks in 10000 seconds, so the block time is 10 seconds at the same target.
+        let epochs = vec![target::Epoch::new(1, COMPACT, 1000, 10_000_000)];
+        let simulation = simulate(&epochs, COMPACT, 1000, &consensus()).unwrap();
+        let difficulty = compact_to_difficulty(COMPACT).unwrap();
+        assert_close(simulation.hash_rate.expected, difficulty / 10.0);
+        let spread = CONFIDENCE_Z * difficulty / 10.0 / 1000f64.sqrt();
+        assert_close(
+            simulation.hash_rate.upper - simulation.hash_rate.expected,
+            spread,
+        );
+        assert_close(simulation.block_time.expected, 10.0);
+        let spread = CONFIDENCE_Z * 10.0 / 1000f64.sqrt();
+        assert_close(
+            simulation.block_time.expected - simulation.block_time.lower,
+            spread,
+        );
+        // Both the randomness of mining and the error of the hash rate.
+        let duration = simulation.first_epoch_duration;
+        assert_close(duration.expected, 10_000.0);
+        assert_close(
+            duration.upper - duration.expected,
+            CONFIDENCE_Z * 200_000f64.sqrt(),
+        );
+        assert_eq!(simulation.verdict, Verdict::Normal);
+    }
+
+    #[test]
+    fn estimate_of_epochs() {
+        let epochs = vec![
+            target::Epoch::new(1, COMPACT, 1000, 10_000_000),
+            target::Epoch::new(2, COMPACT, 1000, 5_000_000),
+        ];
+        let simulation = simulate(&epochs, COMPACT, 1000, &consensus()).unwrap();
+        let difficulty = compact_to_difficulty(COMPACT).unwrap();
+        let rate = difficulty * 2000.0 / 15_000.0;
+        assert_close(simulation.hash_rate.expected, rate);
+        let std_dev = ((difficulty / 10.0 - rate).powi(2) + (difficulty / 5.0 - rate).powi(2))
+            .sqrt()
+            / 2f64.sqrt();
+        assert_close(
+            simulation.hash_rate.upper - simulation.hash_rate.expected,
+            CONFIDENCE_Z * std_dev,
+        );
+        assert_close(simulation.block_time.expected, 7.5);
+        assert!(simulation.block_time.lower < 7.5 && simulation.block_time.upper > 7.5);
+        assert_eq!(simulation.verdict, Verdict::Fast);
+    }
+
+    #[test]
+    fn verdict_of_a_slow_launch() {
+        let epochs = vec![target::Epoch::new(1, COMPACT, 1000, 100_000_000)];
+        let simulation = simulate(&epochs, COMPACT, 1000, &consensus()).unwrap();
+        assert_close(simulation.block_time.expected, 100.0);
+        assert_eq!(simulation.verdict, Verdict::Slow);
+    }
+
+    #[test]
+    fn invalid_epochs() {
+        assert!(simulate(&[], COMPACT, 1000, &consensus()).is_err());
+        let epochs = vec![target::Epoch::new(1, COMPACT, 1000, 0)];
+        assert!(simulate(&epochs, COMPACT, 1000, &consensus()).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+// Copyright (C) 2019 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io::Write;
+
+use ckb_chain_spec::{ChainSpec, IssuedCell};
+use failure::Fail;
+
+use uckb_jsonrpc_client::interfaces::types::{core, packed, prelude::*, rpc};
+
+use crate::{
+    arguments, client,
+    error::{DataError, DataErrorKind, Error, Result},
+    issued, manifest,
+    module::{config, network::Network},
+    template,
+};
+
+/// An error from `ckb-chain-spec`, only the message is kept since it is not thread safe.
+#[derive(Debug, Fail)]
+#[fail(display = "{}", _0)]
+pub struct SpecError(String);
+
+/// Fills a `ChainSpec` from the configuration, the base spec of the network provides the
+/// system cells, the dep groups and the PoW.
+pub struct SpecBuilder<'a> {
+    network: Network,
+    cfg: &'a config::Configuration,
+}
+
+fn spec_error(file: &str, reason: &str) -> DataError {
+    DataError::new(file, DataErrorKind::Unexpected(reason.to_owned()))
+}
+
+impl<'a> SpecBuilder<'a> {
+    pub fn new(network: Network, cfg: &'a config::Configuration) -> Self {
+        Self { network, cfg }
+    }
+
+    pub fn build(&self) -> Result<ChainSpec> {
+        let file = format!("built-in {} base spec", self.network);
+        let mut spec: ChainSpec = toml::from_str(self.network.spec_base())
+            .map_err(|err| spec_error(&file, "failed to parse").caused_by(err))?;
+        let cfg = self.cfg;
+        spec.name = cfg.name.clone();
+        spec.genesis.timestamp = cfg.timestamp;
+        spec.genesis.compact_target = cfg.compact_target;
+        spec.genesis.genesis_cell.message = cfg.message.clone();
+        spec.genesis.issued_cells = cfg
+            .cells
+            .iter()
+            .map(|cell| {
+                // The cell has the same fields as an issued cell.
+                serde_json::to_value(cell)
+                    .and_then(serde_json::from_value::<IssuedCell>)
+                    .map_err(|err| {
+                        let reason = format!("invalid issued cell {}", cell);
+                        spec_error("configuration", &reason).caused_by(err).into()
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        spec.params.genesis_epoch_length = cfg.genesis_epoch_length;
+        Ok(spec)
+    }
+}
+
+/// Serializes the spec as TOML, all tables are sorted by keys.
+pub fn to_canonical(spec: &ChainSpec, file: &str) -> Result<String> {
+    toml::Value::try_from(spec)
+        .and_then(|value| toml::to_string_pretty(&value))
+        .map_err(|err| {
+            spec_error(file, "failed to serialize")
+                .caused_by(err)
+                .into()
+        })
+}
+
+/// Parses the spec text, then checks that its canonical form is stable through a round trip
+/// and the genesis could be built, the genesis block is returned.
+///
+/// If the spec is built by `SpecBuilder`, the parsed spec should be the same as the built one.
+pub fn self_check(text: &str, file: &str, built: Option<&ChainSpec>) -> Result<core::BlockView> {
+    let spec: ChainSpec =
+        toml::from_str(text).map_err(|err| spec_error(file, "failed to parse").caused_by(err))?;
+    let canonical = to_canonical(&spec, file)?;
+    let reparsed: ChainSpec = toml::from_str(&canonical)
+        .map_err(|err| spec_error(file, "failed to parse the canonical form").caused_by(err))?;
+    if to_canonical(&reparsed, file)? != canonical {
+        return Err(spec_error(file, "the canonical form is changed by a round trip").into());
+    }
+    if let Some(built) = built {
+        if to_canonical(built, file)? != canonical {
+            return Err(spec_error(file, "the parsed spec differs from the built spec").into());
+        }
+    }
+    let build = |spec: &ChainSpec| {
+        spec.build_genesis().map_err(|err| {
+            spec_error(file, "failed to build the genesis").caused_by(SpecError(err.to_string()))
+        })
+    };
+    let block = build(&spec)?;
+    let block_reparsed = build(&reparsed)?;
+    if block.hash() != block_reparsed.hash() {
+        let reason = format!(
+            "the genesis hash is changed by a round trip ({:#x} != {:#x})",
+            block.hash(),
+            block_reparsed.hash()
+        );
+        return Err(spec_error(file, &reason).into());
+    }
+    Ok(block)
+}
+
+/// Writes the genesis block as the JSON of CKB RPC and as the molecule bytes.
+///
+/// The hashes of the outputs data are added into each transaction of the JSON, the unknown
+/// fields are ignored when it is parsed as an RPC block.
+fn emit_block(args: &arguments::Arguments, block: &core::BlockView) -> Result<()> {
+    if let Some(file) = args.block_json() {
+        let mut json = serde_json::to_value(rpc::BlockView::from(block.to_owned()))?;
+        let txs_json = json
+            .get_mut("transactions")
+            .and_then(serde_json::Value::as_array_mut)
+            .ok_or_else(|| {
+                Error::Unreachable("the block is not serialized with transactions".to_owned())
+            })?;
+        for (tx_json, tx) in txs_json.iter_mut().zip(block.transactions()) {
+            let hashes = tx
+                .outputs_data()
+                .into_iter()
+                .map(|data| {
+                    format!(
+                        "{:#x}",
+                        packed::CellOutput::calc_data_hash(&data.raw_data())
+                    )
+                })
+                .collect::<Vec<_>>();
+            tx_json["outputs_data_hash"] = serde_json::Value::from(hashes);
+        }
+        serde_json::to_writer_pretty(&mut *file.write(), &json)?;
+    }
+    if let Some(file) = args.block_mol() {
+        file.write().write_all(block.data().as_slice())?;
+    }
+    if args.block_json().is_some() || args.block_mol().is_some() {
+        log::info!(
+            "genesis block: {} transactions, {} bytes",
+            block.transactions().len(),
+            block.data().as_slice().len()
+        );
+    }
+    Ok(())
+}
+
+/// Generates the spec, by the template if it is specified, otherwise by the builder.
+pub fn generate(
+    args: &arguments::Arguments,
+    chain_data: &client::ChainData,
+    cfg: &config::Configuration,
+) -> Result<()> {
+    let (file, text, built) = if let Some(path) = args.template() {
+        (path.to_owned(), template::fill(args, cfg, path)?, None)
+    } else {
+        let file = "generated spec".to_owned();
+        let spec = SpecBuilder::new(args.network(), cfg).build()?;
+        let text = to_canonical(&spec, &file)?;
+        (file, text, Some(spec))
+    };
+    let block = self_check(&text, &file, built.as_ref())?;
+    log::info!("Genesis Hash: {:#x}", block.hash());
+    emit_block(args, &block)?;
+    let issued = issued::Issued::new(&block, cfg, &args.chain().scripts)?;
+    if let Some(file) = args.cells_csv() {
+        issued.write_csv(&mut *file.write())?;
+    }
+    if let Some(file) = args.cells_json() {
+        issued.write_json(&mut *file.write())?;
+    }
+    args.output().write().write_all(text.as_bytes())?;
+    if let Some(file) = args.manifest() {
+        manifest::Manifest::new(args, chain_data, &text, &block)?.write_json(&mut *file.write())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn self_check_against_the_built_spec() {
+        let file = "test spec";
+        let mut spec: ChainSpec = toml::from_str(Network::Dev.spec_base()).unwrap();
+        let text = to_canonical(&spec, file).unwrap();
+        let block = self_check(&text, file, None).unwrap();
+        assert_eq!(
+            self_check(&text, file, Some(&spec)).unwrap().hash(),
+            block.hash()
+        );
+        spec.name = format!("{}_changed", spec.name);
+        match self_check(&text, file, Some(&spec)) {
+            Err(Error::Data(err)) => assert_eq!(err.file(), Some(file)),
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("should not pass since the spec is changed"),
+        }
+    }
+}
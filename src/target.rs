@@ -0,0 +1,348 @@
+// Copyright (C) 2019 Boyu Yang
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{fmt, io, str};
+
+use property::Property;
+use serde_derive::Serialize;
+
+use uckb_jsonrpc_client::interfaces::types::{utilities, U256};
+
+use crate::{
+    error::{DataError, DataErrorKind, Error, Result},
+    module::token::Token,
+};
+
+/// The file of the errors in the epochs.
+const EPOCHS: &str = "epochs of the chain";
+
+/// How to average the difficulties of the epochs in the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Averaging {
+    /// All epochs have the same weight.
+    Mean,
+    /// Weighted by the duration of each epoch.
+    Duration,
+    /// Weighted by the count of blocks in each epoch.
+    Blocks,
+    /// The median of the difficulties.
+    Median,
+}
+
+/// How to compute the compact target of the genesis epoch.
+#[derive(Debug, Clone, Copy, Property)]
+pub struct TargetPolicy {
+    window: u64,
+    averaging: Averaging,
+    fixed: Option<u32>,
+}
+
+/// An epoch before the genesis epoch, its difficulty is a sample of the hash rate.
+#[derive(Debug, Clone, PartialEq, Eq, Property)]
+pub struct Epoch {
+    number: u64,
+    compact_target: u32,
+    length: u64,
+    // In milliseconds, from the first block of this epoch to the first block of the next epoch.
+    duration: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct EpochRow {
+    number: u64,
+    compact_target: String,
+    difficulty: String,
+    length: u64,
+    duration_ms: u64,
+    weight: Option<u64>,
+}
+
+/// How the compact target is computed, it is saved beside the spec.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    averaging: String,
+    window: u64,
+    epochs: Vec<EpochRow>,
+    average_difficulty: String,
+    chain_total_reward: String,
+    reward_pool: String,
+    fixed: bool,
+    difficulty: String,
+    compact_target: String,
+    #[serde(skip)]
+    target: u32,
+}
+
+/// An error in the epochs which are fetched from the chain.
+pub fn epochs_error(reason: String) -> Error {
+    DataError::new(EPOCHS, DataErrorKind::Unexpected(reason)).into()
+}
+
+impl fmt::Display for Averaging {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Mean => write!(f, "mean"),
+            Self::Duration => write!(f, "duration"),
+            Self::Blocks => write!(f, "blocks"),
+            Self::Median => write!(f, "median"),
+        }
+    }
+}
+
+impl str::FromStr for Averaging {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "mean" => Ok(Self::Mean),
+            "duration" => Ok(Self::Duration),
+            "blocks" => Ok(Self::Blocks),
+            "median" => Ok(Self::Median),
+            _ => Err(Error::InvalidAveraging(s.to_owned())),
+        }
+    }
+}
+
+impl TargetPolicy {
+    pub fn new(window: u64, averaging: Averaging, fixed: Option<u32>) -> Self {
+        Self {
+            window,
+            averaging,
+            fixed,
+        }
+    }
+
+    /// The weight of an epoch, the median has no weights.
+    fn weight(&self, epoch: &Epoch) -> Option<u64> {
+        match self.averaging {
+            Averaging::Mean => Some(1),
+            Averaging::Duration => Some(epoch.duration),
+            Averaging::Blocks => Some(epoch.length),
+            Averaging::Median => None,
+        }
+    }
+
+    fn average(&self, epochs: &[Epoch]) -> Result<U256> {
+        if epochs.is_empty() {
+            return Err(epochs_error("no epochs to average".to_owned()));
+        }
+        if self.averaging == Averaging::Median {
+            let mut difficulties = epochs.iter().map(Epoch::difficulty).collect::<Vec<_>>();
+            difficulties.sort();
+            let mid = difficulties.len() / 2;
+            let median = if difficulties.len() % 2 == 0 {
+                (difficulties[mid - 1].clone() + difficulties[mid].clone()) / U256::from(2u8)
+            } else {
+                difficulties[mid].clone()
+            };
+            return Ok(median);
+        }
+        let mut weighted = Vec::with_capacity(epochs.len());
+        let mut total_weight = 0u64;
+        for epoch in epochs {
+            let weight = self.weight(epoch).unwrap_or(1);
+            weighted.push(epoch.difficulty() * U256::from(weight));
+            total_weight = total_weight.checked_add(weight).ok_or_else(|| {
+                epochs_error(format!("overflow when summing {} weights", self.averaging))
+            })?;
+        }
+        if total_weight == 0 {
+            return Err(epochs_error(format!(
+                "the sum of {} weights is zero",
+                self.averaging
+            )));
+        }
+        Ok(weighted.into_iter().sum::<U256>() / U256::from(total_weight))
+    }
+
+    /// Computes the compact target of the genesis epoch.
+    ///
+    /// The average difficulty is scaled by 3/2, then by the ratio of the total rewards of all
+    /// miners to the reward pool of round 5.3, unless a fixed compact target is chosen.
+    pub fn calculate(
+        &self,
+        epochs: &[Epoch],
+        chain_total_reward: Token,
+        reward_pool: Token,
+    ) -> Result<Report> {
+        let average = self.average(epochs)?;
+        let (difficulty, target) = if let Some(fixed) = self.fixed {
+            (utilities::compact_to_difficulty(fixed), fixed)
+        } else {
+            let difficulty = average.clone() * U256::from(3u8) / U256::from(2u8)
+                * U256::from(chain_total_reward.shannons())
+                / U256::from(reward_pool.shannons());
+            let target = utilities::difficulty_to_compact(difficulty.clone());
+            (difficulty, target)
+        };
+        let epochs = epochs
+            .iter()
+            .map(|epoch| EpochRow {
+                number: epoch.number,
+                compact_target: format!("{:#x}", epoch.compact_target),
+                difficulty: epoch.difficulty().to_string(),
+                length: epoch.length,
+                duration_ms: epoch.duration,
+                weight: self.weight(epoch),
+            })
+            .collect();
+        Ok(Report {
+            averaging: self.averaging.to_string(),
+            window: self.window,
+            epochs,
+            average_difficulty: average.to_string(),
+            chain_total_reward: chain_total_reward.to_string(),
+            reward_pool: reward_pool.to_string(),
+            fixed: self.fixed.is_some(),
+            difficulty: difficulty.to_string(),
+            compact_target: format!("{:#x}", target),
+            target,
+        })
+    }
+}
+
+impl Epoch {
+    pub fn new(number: u64, compact_target: u32, length: u64, duration: u64) -> Self {
+        Self {
+            number,
+            compact_target,
+            length,
+            duration,
+        }
+    }
+
+    pub fn difficulty(&self) -> U256 {
+        utilities::compact_to_difficulty(self.compact_target)
+    }
+}
+
+impl Report {
+    pub fn target(&self) -> u32 {
+        self.target
+    }
+
+    /// Prints how the compact target is computed.
+    pub fn log(&self) {
+        log::info!(
+            "target: {} of difficulties in {} epochs",
+            self.averaging,
+            self.window
+        );
+        for epoch in &self.epochs {
+            let weight = epoch.weight.map(|w| format!(", weight {}", w));
+            log::info!(
+                "    epoch {}: compact target {}, difficulty {}, length {}, duration {} ms{}",
+                epoch.number,
+                epoch.compact_target,
+                epoch.difficulty,
+                epoch.length,
+                epoch.duration_ms,
+                weight.unwrap_or_default()
+            );
+        }
+        log::info!("    average difficulty = {}", self.average_difficulty);
+        if self.fixed {
+            log::warn!(
+                "    the compact target is fixed to {}, the average is ignored",
+                self.compact_target
+            );
+        } else {
+            log::info!(
+                "    scaled by 3/2 and by total reward {} / reward pool {}",
+                self.chain_total_reward,
+                self.reward_pool
+            );
+        }
+        log::info!(
+            "    final difficulty = {}, compact target = {}",
+            self.difficulty,
+            self.compact_target
+        );
+    }
+
+    pub fn write_json<W: io::Write>(&self, writer: W) -> Result<()> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn epochs(samples: &[(u32, u64, u64)]) -> Vec<Epoch> {
+        samples
+            .iter()
+            .enumerate()
+            .map(|(number, (compact, length, duration))| {
+                Epoch::new(number as u64, *compact, *length, *duration)
+            })
+            .collect()
+    }
+
+    fn average(averaging: Averaging, epochs: &[Epoch]) -> Result<U256> {
+        TargetPolicy::new(epochs.len() as u64, averaging, None).average(epochs)
+    }
+
+    fn difficulties(epochs: &[Epoch]) -> Vec<U256> {
+        epochs.iter().map(Epoch::difficulty).collect()
+    }
+
+    #[test]
+    fn parse_averaging() {
+        for averaging in &[
+            Averaging::Mean,
+            Averaging::Duration,
+            Averaging::Blocks,
+            Averaging::Median,
+        ] {
+            assert_eq!(
+                averaging.to_string().parse::<Averaging>().unwrap(),
+                *averaging
+            );
+        }
+        assert!("average".parse::<Averaging>().is_err());
+    }
+
+    #[test]
+    fn median_of_odd_and_even_windows() {
+        let odd = epochs(&[
+            (0x1a08_a97e, 1000, 1),
+            (0x1a10_0000, 1000, 1),
+            (0x1a04_0000, 1000, 1),
+        ]);
+        let d = difficulties(&odd);
+        // The difficulty is larger if the target is smaller.
+        assert_eq!(average(Averaging::Median, &odd).unwrap(), d[0]);
+
+        let even = &odd[1..];
+        let d = difficulties(even);
+        let expected = (d[0].clone() + d[1].clone()) / U256::from(2u8);
+        assert_eq!(average(Averaging::Median, even).unwrap(), expected);
+    }
+
+    #[test]
+    fn weighted_averages() {
+        let epochs = epochs(&[(0x1a10_0000, 1000, 3_000), (0x1a04_0000, 3000, 1_000)]);
+        let d = difficulties(&epochs);
+        let mean = (d[0].clone() + d[1].clone()) / U256::from(2u8);
+        assert_eq!(average(Averaging::Mean, &epochs).unwrap(), mean);
+        let by_duration = (d[0].clone() * U256::from(3u8) + d[1].clone()) / U256::from(4u8);
+        assert_eq!(average(Averaging::Duration, &epochs).unwrap(), by_duration);
+        let by_blocks = (d[0].clone() + d[1].clone() * U256::from(3u8)) / U256::from(4u8);
+        assert_eq!(average(Averaging::Blocks, &epochs).unwrap(), by_blocks);
+    }
+
+    #[test]
+    fn invalid_windows() {
+        assert!(average(Averaging::Mean, &[]).is_err());
+        assert!(average(Averaging::Median, &[]).is_err());
+        let no_duration = epochs(&[(0x1a10_0000, 1000, 0), (0x1a04_0000, 1000, 0)]);
+        assert!(average(Averaging::Duration, &no_duration).is_err());
+        assert!(average(Averaging::Mean, &no_duration).is_ok());
+    }
+}
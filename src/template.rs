@@ -6,22 +6,147 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::io::Write;
+use std::{collections::BTreeSet, fs};
 
-use crate::{arguments, data, error::Result, module::config};
+use crate::{
+    arguments,
+    error::{DataError, DataErrorKind, Error, Result},
+    module::config,
+};
+
+/// Collects the root names of all fields which are referred by a template.
+///
+/// The names which are defined by `for` and `with` blocks are excluded.
+fn referred_fields(template: &str) -> BTreeSet<String> {
+    let mut fields = BTreeSet::new();
+    let mut scopes: Vec<String> = Vec::new();
+    let mut refer = |path: &str, scopes: &[String]| {
+        let name = path.split('.').next().unwrap_or_default().trim();
+        if !name.is_empty() && !name.starts_with('@') && !scopes.iter().any(|s| s == name) {
+            fields.insert(name.to_owned());
+        }
+    };
+    let mut rest = template;
+    while let Some(pos) = rest.find('{') {
+        if rest[..pos].ends_with('\\') {
+            rest = &rest[pos + 1..];
+            continue;
+        }
+        let tag = &rest[pos..];
+        if tag.starts_with("{{") {
+            let end = tag.find("}}").unwrap_or_else(|| tag.len());
+            let words = tag[2..end].split_whitespace().collect::<Vec<_>>();
+            match words.as_slice() {
+                ["for", name, "in", path] | ["with", path, "as", name] => {
+                    refer(path, &scopes);
+                    scopes.push((*name).to_owned());
+                }
+                ["endfor"] | ["endwith"] => {
+                    scopes.pop();
+                }
+                ["if", "not", path]
+                | ["if", path]
+                | ["else", "if", "not", path]
+                | ["else", "if", path]
+                | ["call", _, "with", path] => refer(path, &scopes),
+                _ => {}
+            }
+            rest = &tag[(end + 2).min(tag.len())..];
+        } else if tag.starts_with("{#") {
+            let end = tag.find("#}").unwrap_or_else(|| tag.len());
+            rest = &tag[(end + 2).min(tag.len())..];
+        } else {
+            let end = tag.find('}').unwrap_or_else(|| tag.len());
+            let expr = tag[1..end].split('|').next().unwrap_or_default();
+            refer(expr, &scopes);
+            rest = &tag[(end + 1).min(tag.len())..];
+        }
+    }
+    fields
+}
+
+/// Renders the spec by a user template, all fields of the configuration and the custom
+/// variables could be used.
+pub fn fill(
+    args: &arguments::Arguments,
+    cfg: &config::Configuration,
+    file: &str,
+) -> Result<String> {
+    let template = fs::read_to_string(file)?;
+
+    let mut context = serde_json::to_value(cfg)?;
+    {
+        let object = context.as_object_mut().ok_or_else(|| {
+            Error::Unreachable("the configuration is not serialized as an object".to_owned())
+        })?;
+        for (key, value) in args.vars() {
+            if object.contains_key(key) {
+                return Err(Error::InvalidVariable(format!(
+                    "{} (a field of the configuration)",
+                    key
+                )));
+            }
+            object.insert(key.to_owned(), value.to_owned());
+        }
+        let undefined = referred_fields(&template)
+            .into_iter()
+            .filter(|field| !object.contains_key(field))
+            .collect::<Vec<_>>();
+        if !undefined.is_empty() {
+            let reason = format!("undefined fields [{}]", undefined.join(", "));
+            return Err(DataError::new(file, DataErrorKind::Unexpected(reason)).into());
+        }
+    }
 
-pub fn fill(args: &arguments::Arguments, cfg: &config::Configuration) -> Result<()> {
     let tag = "specs";
     let mut tt = tinytemplate::TinyTemplate::new();
-    tt.add_template(tag, data::SPECS_TEMPLATE).unwrap();
-    let rendered = tt.render(tag, cfg).unwrap();
-    let spec: ckb_chain_spec::ChainSpec = toml::from_slice(&rendered.as_bytes())?;
-    let hash = spec.build_genesis().unwrap().hash();
-    log::info!("Genesis Hash: {:#x}", hash);
-    {
-        let mut file = args.output().write();
-        file.write_all(rendered.as_str().as_bytes())?;
-        file.sync_all()?;
+    tt.add_template(tag, &template).map_err(|err| {
+        let reason = format!("failed to parse, {}", err);
+        DataError::new(file, DataErrorKind::Unexpected(reason))
+    })?;
+    let rendered = tt.render(tag, &context).map_err(|err| {
+        let reason = format!("failed to render, {}", err);
+        DataError::new(file, DataErrorKind::Unexpected(reason))
+    })?;
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(template: &str) -> Vec<String> {
+        referred_fields(template).into_iter().collect()
+    }
+
+    #[test]
+    fn plain_values_and_formatters() {
+        let template =
+            "name = \"{name}\"\ntimestamp = {timestamp}\nmessage = \"{ message | unescaped }\"";
+        assert_eq!(fields(template), vec!["message", "name", "timestamp"]);
+        assert_eq!(fields("{cfg.genesis.epoch_length}"), vec!["cfg"]);
+        assert!(fields("no fields").is_empty());
+    }
+
+    #[test]
+    fn scoped_names_are_excluded() {
+        let template = "{{ for cell in cells }}{cell.capacity} {@index} {version}{{ endfor }}\
+                        {{ with lock as l }}{l.args}{{ endwith }}{cell}";
+        assert_eq!(fields(template), vec!["cell", "cells", "lock", "version"]);
+    }
+
+    #[test]
+    fn conditions_and_calls() {
+        let template = "{{ if dev }}a{{ else if not testnet }}b{{ endif }}\
+                        {{ call row with header }}";
+        assert_eq!(fields(template), vec!["dev", "header", "testnet"]);
+    }
+
+    #[test]
+    fn escapes_and_comments() {
+        assert!(fields("\\{name} {# {hidden} #}").is_empty());
+        assert_eq!(fields("{{ if ok }}"), vec!["ok"]);
+        // An unclosed tag is left to the template engine.
+        assert_eq!(fields("{name"), vec!["name"]);
     }
-    Ok(())
 }